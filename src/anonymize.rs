@@ -0,0 +1,316 @@
+//! `--anonymize`: renames user-defined Rust functions, types, constants, and variables to
+//! placeholders like `fn_1`, `type_2`, and `var_3`, so a prompt's structure can be shared
+//! without its names.
+//!
+//! Names are gathered from every Rust file before any file is rewritten, so a function gets
+//! the same placeholder wherever it is called. Renaming goes by name rather than by scope,
+//! which keeps it consistent but makes it a best effort: methods, fields, enum variants,
+//! and modules keep their names, as does anything reached through an external path such as
+//! `std::fs::read`. String contents, including inline format arguments, are left alone.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Group, TokenStream, TokenTree};
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::{FieldPat, FieldValue, Ident, ItemUse, Member, UseTree};
+
+#[derive(Default)]
+pub struct Anonymizer {
+    /// Original name to placeholder
+    names: HashMap<String, String>,
+    /// Placeholders handed out so far, per prefix
+    counts: HashMap<&'static str, usize>,
+}
+
+impl Anonymizer {
+    /// Records the names defined in a Rust file. Files that don't parse are skipped.
+    pub fn collect(&mut self, code: &str) {
+        if let Ok(file) = syn::parse_file(code) {
+            Collector { anonymizer: self }.visit_file(&file);
+        }
+    }
+
+    /// Replaces every recorded name in `file` with its placeholder.
+    pub fn rename(&self, file: &mut syn::File) {
+        Renamer { names: &self.names }.visit_file_mut(file);
+    }
+
+    fn define(&mut self, ident: &Ident, prefix: &'static str) {
+        let name = ident.to_string();
+        // The entry point has to keep its name
+        if name == "main" || self.names.contains_key(&name) {
+            return;
+        }
+        let count = self.counts.entry(prefix).or_insert(0);
+        *count += 1;
+        self.names.insert(name, format!("{}_{}", prefix, count));
+    }
+}
+
+/// Finds the names a file defines.
+struct Collector<'a> {
+    anonymizer: &'a mut Anonymizer,
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.anonymizer.define(&item.sig.ident, "fn");
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_item_struct(&mut self, item: &'ast syn::ItemStruct) {
+        self.anonymizer.define(&item.ident, "type");
+        visit::visit_item_struct(self, item);
+    }
+
+    fn visit_item_enum(&mut self, item: &'ast syn::ItemEnum) {
+        self.anonymizer.define(&item.ident, "type");
+        visit::visit_item_enum(self, item);
+    }
+
+    fn visit_item_union(&mut self, item: &'ast syn::ItemUnion) {
+        self.anonymizer.define(&item.ident, "type");
+        visit::visit_item_union(self, item);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast syn::ItemTrait) {
+        self.anonymizer.define(&item.ident, "type");
+        visit::visit_item_trait(self, item);
+    }
+
+    fn visit_item_type(&mut self, item: &'ast syn::ItemType) {
+        self.anonymizer.define(&item.ident, "type");
+        visit::visit_item_type(self, item);
+    }
+
+    fn visit_item_const(&mut self, item: &'ast syn::ItemConst) {
+        self.anonymizer.define(&item.ident, "const");
+        visit::visit_item_const(self, item);
+    }
+
+    fn visit_item_static(&mut self, item: &'ast syn::ItemStatic) {
+        self.anonymizer.define(&item.ident, "const");
+        visit::visit_item_static(self, item);
+    }
+
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        // Capitalized identifier patterns are usually unit variants like `None`
+        if pat.ident.to_string().starts_with(|c: char| c.is_lowercase() || c == '_') {
+            self.anonymizer.define(&pat.ident, "var");
+        }
+        visit::visit_pat_ident(self, pat);
+    }
+}
+
+/// Rewrites definitions and references to the collected names.
+struct Renamer<'a> {
+    names: &'a HashMap<String, String>,
+}
+
+impl Renamer<'_> {
+    fn rename(&self, ident: &mut Ident) -> bool {
+        match self.names.get(&ident.to_string()) {
+            Some(placeholder) => {
+                *ident = Ident::new(placeholder, ident.span());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Renames identifiers inside a macro invocation, except those after a `.`, which are
+    /// methods or fields.
+    fn rename_tokens(&self, tokens: TokenStream) -> TokenStream {
+        let mut after_dot = false;
+        tokens
+            .into_iter()
+            .map(|tree| {
+                let tree = match tree {
+                    TokenTree::Ident(mut ident) => {
+                        if !after_dot {
+                            self.rename(&mut ident);
+                        }
+                        TokenTree::Ident(ident)
+                    }
+                    TokenTree::Group(group) => {
+                        let mut renamed = Group::new(group.delimiter(), self.rename_tokens(group.stream()));
+                        renamed.set_span(group.span());
+                        TokenTree::Group(renamed)
+                    }
+                    other => other,
+                };
+                after_dot = matches!(&tree, TokenTree::Punct(punct) if punct.as_char() == '.');
+                tree
+            })
+            .collect()
+    }
+
+    fn rename_use_tree(&self, tree: &mut UseTree) {
+        match tree {
+            UseTree::Path(path) => self.rename_use_tree(&mut path.tree),
+            UseTree::Name(name) => {
+                self.rename(&mut name.ident);
+            }
+            UseTree::Rename(rename) => {
+                self.rename(&mut rename.ident);
+            }
+            UseTree::Group(group) => {
+                for tree in &mut group.items {
+                    self.rename_use_tree(tree);
+                }
+            }
+            UseTree::Glob(_) => {}
+        }
+    }
+}
+
+/// True for the first segment of a path into the current crate.
+fn is_local_root(ident: &Ident) -> bool {
+    ident == "crate" || ident == "self" || ident == "super"
+}
+
+impl VisitMut for Renamer<'_> {
+    fn visit_item_fn_mut(&mut self, item: &mut syn::ItemFn) {
+        self.rename(&mut item.sig.ident);
+        visit_mut::visit_item_fn_mut(self, item);
+    }
+
+    fn visit_item_struct_mut(&mut self, item: &mut syn::ItemStruct) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_struct_mut(self, item);
+    }
+
+    fn visit_item_enum_mut(&mut self, item: &mut syn::ItemEnum) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_enum_mut(self, item);
+    }
+
+    fn visit_item_union_mut(&mut self, item: &mut syn::ItemUnion) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_union_mut(self, item);
+    }
+
+    fn visit_item_trait_mut(&mut self, item: &mut syn::ItemTrait) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_trait_mut(self, item);
+    }
+
+    fn visit_item_type_mut(&mut self, item: &mut syn::ItemType) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_type_mut(self, item);
+    }
+
+    fn visit_item_const_mut(&mut self, item: &mut syn::ItemConst) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_const_mut(self, item);
+    }
+
+    fn visit_item_static_mut(&mut self, item: &mut syn::ItemStatic) {
+        self.rename(&mut item.ident);
+        visit_mut::visit_item_static_mut(self, item);
+    }
+
+    fn visit_pat_ident_mut(&mut self, pat: &mut syn::PatIdent) {
+        self.rename(&mut pat.ident);
+        visit_mut::visit_pat_ident_mut(self, pat);
+    }
+
+    fn visit_path_mut(&mut self, path: &mut syn::Path) {
+        // `foo`, `Foo::new`, and `crate::a::foo` refer to our own names, but in
+        // `std::fs::read` or `::log::info` even a name we know means something else
+        let last = path.segments.len().saturating_sub(1);
+        let local = path.leading_colon.is_none()
+            && path.segments.first().is_some_and(|segment| is_local_root(&segment.ident));
+        for (i, segment) in path.segments.iter_mut().enumerate() {
+            if path.leading_colon.is_some() || (i > 0 && !local) {
+                break;
+            }
+            // Anything before the last segment must be a type to be ours
+            let is_type = self
+                .names
+                .get(&segment.ident.to_string())
+                .is_some_and(|placeholder| placeholder.starts_with("type_"));
+            if i == last || is_type {
+                self.rename(&mut segment.ident);
+            }
+        }
+        visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_item_use_mut(&mut self, item: &mut ItemUse) {
+        if item.leading_colon.is_none()
+            && let UseTree::Path(path) = &mut item.tree
+            && is_local_root(&path.ident)
+        {
+            self.rename_use_tree(&mut path.tree);
+        }
+    }
+
+    fn visit_field_value_mut(&mut self, field: &mut FieldValue) {
+        // `Foo { x }` needs to become `Foo { x: var_1 }`
+        if field.colon_token.is_none()
+            && let Member::Named(ident) = &field.member
+            && self.names.contains_key(&ident.to_string())
+        {
+            field.colon_token = Some(Default::default());
+        }
+        visit_mut::visit_field_value_mut(self, field);
+    }
+
+    fn visit_field_pat_mut(&mut self, field: &mut FieldPat) {
+        // Likewise `let Foo { x } = ...` becomes `let Foo { x: var_1 } = ...`
+        if field.colon_token.is_none()
+            && let Member::Named(ident) = &field.member
+            && self.names.contains_key(&ident.to_string())
+        {
+            field.colon_token = Some(Default::default());
+        }
+        visit_mut::visit_field_pat_mut(self, field);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        visit_mut::visit_macro_mut(self, mac);
+        mac.tokens = self.rename_tokens(std::mem::take(&mut mac.tokens));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anonymize(files: &[&str]) -> Vec<String> {
+        let mut anonymizer = Anonymizer::default();
+        files.iter().for_each(|code| anonymizer.collect(code));
+        files
+            .iter()
+            .map(|code| {
+                let mut file = syn::parse_file(code).unwrap();
+                anonymizer.rename(&mut file);
+                prettyplease::unparse(&file)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn references_to_a_function_share_its_placeholder() {
+        let renamed = anonymize(&[
+            "fn parse_config(path: &str) -> Settings { load(path) }\nfn main() { let settings = parse_config(\"a\"); parse_config(\"b\"); }\n",
+            "fn load(path: &str) -> Settings { Settings }\nstruct Settings;\n",
+        ]);
+        assert_eq!(renamed[0].matches("fn_1").count(), 3, "{}", renamed[0]);
+        assert!(!renamed[0].contains("parse_config") && !renamed[0].contains("settings"), "{}", renamed[0]);
+        assert!(renamed[0].contains("fn main()"), "{}", renamed[0]);
+        // Names defined in another file are renamed the same way
+        assert!(renamed[0].contains("fn_2(var_1)") && renamed[1].contains("fn fn_2("), "{:?}", renamed);
+        assert!(renamed[1].contains("struct type_1;"), "{}", renamed[1]);
+    }
+
+    #[test]
+    fn keeps_strings_and_external_paths() {
+        let renamed = anonymize(&["fn read(name: &str) -> String { std::fs::read_to_string(\"read name\").unwrap() }\n"]);
+        assert!(renamed[0].contains("\"read name\""), "{}", renamed[0]);
+        assert!(renamed[0].contains("std::fs::read_to_string"), "{}", renamed[0]);
+        assert!(renamed[0].contains("String"), "{}", renamed[0]);
+    }
+}
@@ -0,0 +1,129 @@
+//! `--strip-attributes`: removes outer attributes like `#[derive(Debug)]` and
+//! `#[serde(rename = "..")]` from Rust items and their fields, variants, and members.
+//!
+//! Doc comments are attributes too, but they're left to `--remove-docs`. Inner attributes
+//! (`#![allow(..)]`) and attributes on expressions and statements are kept.
+
+use syn::visit_mut::{self, VisitMut};
+use syn::{Attribute, AttrStyle, ForeignItem, ImplItem, Item, TraitItem};
+
+/// Strips outer attributes throughout `file`: all of them when `paths` is empty, otherwise
+/// those whose path is listed, e.g. `derive` or `serde`.
+pub fn strip(file: &mut syn::File, paths: &[String]) {
+    Stripper { paths }.visit_file_mut(file);
+}
+
+struct Stripper<'a> {
+    paths: &'a [String],
+}
+
+impl Stripper<'_> {
+    fn strip(&self, attrs: &mut Vec<Attribute>) {
+        attrs.retain(|attr| {
+            let path = attr
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect::<Vec<_>>()
+                .join("::");
+            let listed = self.paths.is_empty() || self.paths.contains(&path);
+            !matches!(attr.style, AttrStyle::Outer) || path == "doc" || !listed
+        });
+    }
+}
+
+impl VisitMut for Stripper<'_> {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        let attrs = match item {
+            Item::Const(item) => &mut item.attrs,
+            Item::Enum(item) => &mut item.attrs,
+            Item::ExternCrate(item) => &mut item.attrs,
+            Item::Fn(item) => &mut item.attrs,
+            Item::ForeignMod(item) => &mut item.attrs,
+            Item::Impl(item) => &mut item.attrs,
+            Item::Macro(item) => &mut item.attrs,
+            Item::Macro2(item) => &mut item.attrs,
+            Item::Mod(item) => &mut item.attrs,
+            Item::Static(item) => &mut item.attrs,
+            Item::Struct(item) => &mut item.attrs,
+            Item::Trait(item) => &mut item.attrs,
+            Item::TraitAlias(item) => &mut item.attrs,
+            Item::Type(item) => &mut item.attrs,
+            Item::Union(item) => &mut item.attrs,
+            Item::Use(item) => &mut item.attrs,
+            _ => return visit_mut::visit_item_mut(self, item),
+        };
+        self.strip(attrs);
+        visit_mut::visit_item_mut(self, item);
+    }
+
+    fn visit_impl_item_mut(&mut self, item: &mut ImplItem) {
+        let attrs = match item {
+            ImplItem::Const(item) => &mut item.attrs,
+            ImplItem::Method(item) => &mut item.attrs,
+            ImplItem::Type(item) => &mut item.attrs,
+            ImplItem::Macro(item) => &mut item.attrs,
+            _ => return visit_mut::visit_impl_item_mut(self, item),
+        };
+        self.strip(attrs);
+        visit_mut::visit_impl_item_mut(self, item);
+    }
+
+    fn visit_trait_item_mut(&mut self, item: &mut TraitItem) {
+        let attrs = match item {
+            TraitItem::Const(item) => &mut item.attrs,
+            TraitItem::Method(item) => &mut item.attrs,
+            TraitItem::Type(item) => &mut item.attrs,
+            TraitItem::Macro(item) => &mut item.attrs,
+            _ => return visit_mut::visit_trait_item_mut(self, item),
+        };
+        self.strip(attrs);
+        visit_mut::visit_trait_item_mut(self, item);
+    }
+
+    fn visit_foreign_item_mut(&mut self, item: &mut ForeignItem) {
+        let attrs = match item {
+            ForeignItem::Fn(item) => &mut item.attrs,
+            ForeignItem::Static(item) => &mut item.attrs,
+            ForeignItem::Type(item) => &mut item.attrs,
+            ForeignItem::Macro(item) => &mut item.attrs,
+            _ => return visit_mut::visit_foreign_item_mut(self, item),
+        };
+        self.strip(attrs);
+        visit_mut::visit_foreign_item_mut(self, item);
+    }
+
+    fn visit_field_mut(&mut self, field: &mut syn::Field) {
+        self.strip(&mut field.attrs);
+        visit_mut::visit_field_mut(self, field);
+    }
+
+    fn visit_variant_mut(&mut self, variant: &mut syn::Variant) {
+        self.strip(&mut variant.attrs);
+        visit_mut::visit_variant_mut(self, variant);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stripped(code: &str, paths: &[&str]) -> String {
+        let mut file = syn::parse_file(code).unwrap();
+        strip(&mut file, &paths.iter().map(|path| path.to_string()).collect::<Vec<_>>());
+        prettyplease::unparse(&file)
+    }
+
+    #[test]
+    fn strips_outer_attributes_but_not_docs_or_inner_ones() {
+        let code = "#![allow(dead_code)]\n/// A point\n#[derive(Debug)]\npub struct Point {\n    #[serde(rename = \"x\")]\n    x: i32,\n}\n";
+        assert_eq!(stripped(code, &[]), "#![allow(dead_code)]\n/// A point\npub struct Point {\n    x: i32,\n}\n");
+    }
+
+    #[test]
+    fn only_listed_paths_are_stripped() {
+        let code = "#[derive(Debug)]\n#[serde::skip]\n#[repr(C)]\nenum E {\n    #[default]\n    A,\n}\n";
+        assert_eq!(stripped(code, &["derive", "serde::skip"]), "#[repr(C)]\nenum E {\n    #[default]\n    A,\n}\n");
+    }
+}
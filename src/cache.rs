@@ -0,0 +1,306 @@
+//! On-disk cache of processed sections, so unchanged files skip parsing and minifying.
+//!
+//! Each file gets a directory named by the SHA-256 of its path, holding one entry keyed by
+//! the SHA-256 of its modification time, size, and the options that affect its processing.
+//! Editing a file changes its key, leaving every other file's entry valid, and writing the
+//! new entry removes the stale one. Warnings raised while processing the file are stored
+//! with it, so a cached run reports them too.
+//!
+//! Once a day, entries for files that no longer exist, or that haven't been used in
+//! `MAX_AGE`, are removed so the cache doesn't keep growing.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+
+use crate::{CONFIG_FORMATS, Cli, LANGUAGES, Section, Warning, WarningKind, handlers_for, modified_within, sha256_hex};
+
+/// Names of the warnings kept with an entry.
+const WARNING_KINDS: &[(WarningKind, &str)] = &[
+    (WarningKind::Parse, "parse"),
+    (WarningKind::Lossy, "lossy"),
+    (WarningKind::NotUtf8, "not-utf8"),
+    (WarningKind::Unreadable, "unreadable"),
+    (WarningKind::TooLarge, "too-large"),
+    (WarningKind::Filter, "filter"),
+];
+
+/// How long an entry is kept without being used.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How often the cache is checked for entries to remove.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// File in the cache directory whose modification time says when it was last pruned.
+const PRUNED_MARKER: &str = "pruned";
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Opens the cache in `$CARGO_PROMPT_CACHE_DIR`, or else in the user's cache directory.
+    /// `None` if there's neither.
+    pub fn open() -> Option<Self> {
+        let dir = match std::env::var_os("CARGO_PROMPT_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()?.join("cargo-prompt"),
+        };
+        fs::create_dir_all(&dir).ok()?;
+        let cache = Cache { dir };
+        let marker = cache.dir.join(PRUNED_MARKER);
+        if !modified_within(&marker, PRUNE_INTERVAL) {
+            let _ = fs::write(marker, "");
+            cache.prune();
+        }
+        Some(cache)
+    }
+
+    /// Returns the sections previously produced for `path` with the same options, and the
+    /// warnings raised producing them.
+    pub fn get(&self, path: &Path, args: &Cli) -> Option<(Vec<Section>, Vec<Warning>)> {
+        let (_, entry) = self.entry(path, args)?;
+        let value: Value = serde_json::from_str(&fs::read_to_string(&entry).ok()?).ok()?;
+        let sections = value
+            .get("sections")?
+            .as_array()?
+            .iter()
+            .map(|section| section_from_json(path, section))
+            .collect::<Option<_>>()?;
+        let warnings = value
+            .get("warnings")?
+            .as_array()?
+            .iter()
+            .map(|warning| warning_from_json(path, warning))
+            .collect::<Option<_>>()?;
+        // The entry's modification time says when it was last used, for `prune`
+        let _ = fs::File::options().write(true).open(&entry).and_then(|file| file.set_modified(SystemTime::now()));
+        Some((sections, warnings))
+    }
+
+    /// Stores the sections produced for `path` and the warnings raised producing them, in
+    /// place of any earlier entry for the file. Failures only cost a cache miss later.
+    pub fn put(&self, path: &Path, args: &Cli, sections: &[Section], warnings: &[Warning]) {
+        let Some((file_dir, entry)) = self.entry(path, args) else {
+            return;
+        };
+        let Ok(source) = fs::canonicalize(path) else {
+            return;
+        };
+        let sections: Vec<Value> = sections
+            .iter()
+            .map(|section| {
+                json!({
+                    "fence": section.fence,
+                    "symbols": section.symbols,
+                    "content": section.content,
+                    "summarized": section.summarized,
+                    "source_lines": section.source_lines,
+                })
+            })
+            .collect();
+        let warnings: Vec<Value> = warnings
+            .iter()
+            .filter_map(|warning| {
+                let (_, kind) = WARNING_KINDS.iter().find(|(kind, _)| *kind == warning.kind)?;
+                Some(json!({ "kind": kind, "message": warning.message }))
+            })
+            .collect();
+
+        // Entries for earlier versions of the file, or other options, won't be read again
+        if let Ok(stale) = fs::read_dir(&file_dir) {
+            for stale in stale.flatten() {
+                let _ = fs::remove_file(stale.path());
+            }
+        }
+        if fs::create_dir_all(&file_dir).is_ok() {
+            let entry_json = json!({ "path": source.to_string_lossy(), "sections": sections, "warnings": warnings });
+            let _ = fs::write(entry, entry_json.to_string());
+        }
+    }
+
+    /// Removes entries whose file is gone or that haven't been used in `MAX_AGE`, along with
+    /// the directories left empty.
+    fn prune(&self) {
+        let Ok(file_dirs) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for file_dir in file_dirs.flatten().map(|file_dir| file_dir.path()).filter(|path| path.is_dir()) {
+            for entry in fs::read_dir(&file_dir).into_iter().flatten().flatten().map(|entry| entry.path()) {
+                if !modified_within(&entry, MAX_AGE) || !source_exists(&entry) {
+                    let _ = fs::remove_file(entry);
+                }
+            }
+            // Fails, harmlessly, while the directory still holds a live entry
+            let _ = fs::remove_dir(file_dir);
+        }
+    }
+
+    /// The directory holding `path`'s entry, and the entry for it as it is now with `args`.
+    fn entry(&self, path: &Path, args: &Cli) -> Option<(PathBuf, PathBuf)> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        let canonical = fs::canonicalize(path).ok()?;
+        let file_dir = self.dir.join(sha256_hex(canonical.as_os_str().as_encoded_bytes()));
+
+        // Everything that changes how this file is processed
+        let handlers: Vec<&str> = handlers_for(path, args).iter().map(|handler| handler.name()).collect();
+        let key = format!(
+            "{:?}",
+            (
+                env!("CARGO_PKG_VERSION"),
+                modified.as_nanos(),
+                metadata.len(),
+                handlers,
+                (&args.remove_docs, args.no_minify, args.minify_level, args.encoding),
+                (args.head, args.tail, args.marker.as_ref().map(regex::Regex::as_str), args.context),
+                (args.symbols, args.outline, args.summarize_large, args.keep_license),
+                (args.trim_imports, &args.strip_attributes),
+            )
+        );
+        let entry = file_dir.join(format!("{}.json", sha256_hex(key.as_bytes())));
+        Some((file_dir, entry))
+    }
+}
+
+/// Whether the file `entry` was made for still exists.
+fn source_exists(entry: &Path) -> bool {
+    fs::read_to_string(entry)
+        .ok()
+        .and_then(|entry| serde_json::from_str::<Value>(&entry).ok())
+        .and_then(|entry| entry.get("path")?.as_str().map(PathBuf::from))
+        .is_some_and(|source| source.exists())
+}
+
+fn warning_from_json(path: &Path, value: &Value) -> Option<Warning> {
+    let name = value.get("kind")?.as_str()?;
+    let (kind, _) = WARNING_KINDS.iter().find(|(_, known)| *known == name)?;
+    Some(Warning::new(path, *kind, value.get("message")?.as_str()?))
+}
+
+fn section_from_json(path: &Path, value: &Value) -> Option<Section> {
+    let fence = value.get("fence")?.as_str()?;
+    // Sections borrow their fence label from the language tables
+    let fence = ["", "rust", "javascript"]
+        .into_iter()
+        .chain(LANGUAGES.iter().chain(CONFIG_FORMATS).map(|spec| spec.fence))
+        .find(|known| *known == fence)?;
+    let symbols = match value.get("symbols")? {
+        Value::Null => None,
+        symbols => Some(
+            symbols
+                .as_array()?
+                .iter()
+                .map(|symbol| symbol.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()?,
+        ),
+    };
+    Some(Section {
+        path: path.to_path_buf(),
+        display_path: String::new(),
+        fence,
+        symbols,
+        content: value.get("content")?.as_str()?.to_owned(),
+        summarized: value.get("summarized")?.as_bool()?,
+        crate_name: None,
+        file_info: None,
+        content_stats: None,
+        compact: false,
+        duplicate_of: None,
+        source_lines: value.get("source_lines")?.as_u64()? as usize,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CargoCli;
+    use clap::Parser;
+
+    fn section(path: &Path, content: &str) -> Section {
+        let value = json!({
+            "fence": "rust",
+            "symbols": null,
+            "content": content,
+            "summarized": false,
+            "source_lines": 1,
+        });
+        section_from_json(path, &value).unwrap()
+    }
+
+    #[test]
+    fn editing_a_file_invalidates_only_its_entry() {
+        let project = tempfile::tempdir().unwrap();
+        let cache = Cache { dir: project.path().join("cache") };
+        let CargoCli::Prompt(args) = CargoCli::parse_from(["cargo", "prompt"]);
+        let (a, b) = (project.path().join("a.rs"), project.path().join("b.rs"));
+        fs::write(&a, "fn a() {}").unwrap();
+        fs::write(&b, "fn b() {}").unwrap();
+
+        cache.put(&a, &args, &[section(&a, "fn a(){}")], &[]);
+        cache.put(&b, &args, &[section(&b, "fn b(){}")], &[]);
+        assert!(cache.get(&a, &args).is_some());
+
+        fs::write(&a, "fn a() { todo!() }").unwrap();
+        assert!(cache.get(&a, &args).is_none());
+        let (sections, _) = cache.get(&b, &args).unwrap();
+        assert_eq!(sections[0].content, "fn b(){}");
+    }
+
+    #[test]
+    fn writing_an_entry_removes_the_stale_one() {
+        let project = tempfile::tempdir().unwrap();
+        let cache = Cache { dir: project.path().join("cache") };
+        let CargoCli::Prompt(args) = CargoCli::parse_from(["cargo", "prompt"]);
+        let path = project.path().join("a.rs");
+
+        fs::write(&path, "fn a() {}").unwrap();
+        cache.put(&path, &args, &[section(&path, "fn a(){}")], &[]);
+        fs::write(&path, "fn a() { todo!() }").unwrap();
+        cache.put(&path, &args, &[section(&path, "fn a(){todo!()}")], &[]);
+
+        let (file_dir, _) = cache.entry(&path, &args).unwrap();
+        assert_eq!(fs::read_dir(file_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn keeps_warnings_with_the_entry() {
+        let project = tempfile::tempdir().unwrap();
+        let cache = Cache { dir: project.path().join("cache") };
+        let CargoCli::Prompt(args) = CargoCli::parse_from(["cargo", "prompt"]);
+        let path = project.path().join("a.rs");
+        fs::write(&path, "fn a( {}").unwrap();
+
+        let warning = Warning::new(&path, WarningKind::Parse, "expected `)`");
+        cache.put(&path, &args, &[section(&path, "fn a( {}")], &[warning]);
+
+        let (_, warnings) = cache.get(&path, &args).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].kind == WarningKind::Parse);
+        assert_eq!(warnings[0].message, "expected `)`");
+    }
+
+    #[test]
+    fn pruning_removes_entries_for_deleted_and_unused_files() {
+        let project = tempfile::tempdir().unwrap();
+        let cache = Cache { dir: project.path().join("cache") };
+        let CargoCli::Prompt(args) = CargoCli::parse_from(["cargo", "prompt"]);
+        let [kept, deleted, unused] = ["kept.rs", "deleted.rs", "unused.rs"].map(|name| project.path().join(name));
+        for path in [&kept, &deleted, &unused] {
+            fs::write(path, "fn f() {}").unwrap();
+            cache.put(path, &args, &[section(path, "fn f(){}")], &[]);
+        }
+        let (unused_dir, unused_entry) = cache.entry(&unused, &args).unwrap();
+        let long_ago = SystemTime::now() - MAX_AGE - Duration::from_secs(60);
+        fs::File::options().write(true).open(unused_entry).unwrap().set_modified(long_ago).unwrap();
+        let (deleted_dir, _) = cache.entry(&deleted, &args).unwrap();
+        fs::remove_file(&deleted).unwrap();
+
+        cache.prune();
+        assert!(cache.get(&kept, &args).is_some());
+        assert!(!deleted_dir.exists() && !unused_dir.exists());
+        assert_eq!(fs::read_dir(&cache.dir).unwrap().count(), 1);
+    }
+}
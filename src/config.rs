@@ -0,0 +1,157 @@
+//! `.cargo-prompt.toml`: default options for a project, so long flag combinations don't
+//! need retyping.
+//!
+//! Keys are the long option names, e.g.
+//!
+//! ```toml
+//! python = true
+//! remove-docs = ["python", "js"]
+//! exclude-ext = ["pyw"]
+//! format = "xml"
+//! max-tokens = 100000
+//! ```
+//!
+//! The file is turned into command line arguments placed before the user's own. An option
+//! given on the command line replaces the file's value, lists included, and a flag the
+//! file switches on is switched off by its negation, `--no-<flag>` (or `--<flag>` for a
+//! `--no-<flag>`).
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, CommandFactory, FromArgMatches};
+
+use crate::{CargoCli, Cli, command_line};
+
+/// Name of the config file looked for in the first directory being walked.
+const FILE_NAME: &str = ".cargo-prompt.toml";
+
+/// Applies `--config`, or the project's `.cargo-prompt.toml` when there is one, by parsing
+/// the command line again with the file's options in front.
+pub fn apply(args: Cli) -> anyhow::Result<Cli> {
+    let path = match &args.config {
+        Some(path) => path.clone(),
+        None => match default_path(&args.dirs) {
+            Some(path) => path,
+            None => return Ok(args),
+        },
+    };
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("couldn't read config file {}: {}", path.display(), e))?;
+    let table: toml::Table =
+        toml::from_str(&contents).map_err(|e| anyhow::anyhow!("invalid config file {}: {}", path.display(), e))?;
+
+    let mut command_line = command_line();
+    let command = command();
+    let matches = command.clone().try_get_matches_from(&command_line).unwrap_or_else(|e| e.exit());
+    let prompt = command.find_subcommand("prompt").expect("`prompt` is the only subcommand");
+    let matches = matches.subcommand_matches("prompt").expect("`prompt` is the only subcommand");
+    let table: toml::Table = table.into_iter().filter(|(key, _)| !given(key, prompt, matches)).collect();
+
+    // Right after `prompt`, ahead of everything typed by the user
+    let from_file = to_arguments(&table).map_err(|e| anyhow::anyhow!("in config file {}: {}", path.display(), e))?;
+    let position = 2.min(command_line.len());
+    command_line.splice(position..position, from_file);
+
+    Ok(parse(&command_line).unwrap_or_else(|e| e.exit()))
+}
+
+/// Parses `command_line` as `cargo prompt ...`, accepting the flags' negations.
+pub fn parse(command_line: &[OsString]) -> Result<Cli, clap::Error> {
+    let matches = command().try_get_matches_from(command_line)?;
+    let CargoCli::Prompt(args) = CargoCli::from_arg_matches(&matches)?;
+    Ok(args)
+}
+
+/// The `cargo prompt` command with a hidden negation of every flag that lacks one, which
+/// only matters for leaving out the config file's value.
+fn command() -> clap::Command {
+    CargoCli::command().mut_subcommand("prompt", |prompt| {
+        let longs: Vec<String> = prompt.get_arguments().filter_map(Arg::get_long).map(str::to_string).collect();
+        let negations: Vec<Arg> = prompt
+            .get_arguments()
+            .filter(|arg| matches!(arg.get_action(), ArgAction::SetTrue))
+            .filter_map(|arg| arg.get_long().map(negation))
+            .filter(|negation| !longs.contains(negation))
+            .map(|negation| Arg::new(negation.clone()).long(negation).action(ArgAction::SetTrue).hide(true))
+            .collect();
+        prompt.args(negations)
+    })
+}
+
+/// `no-<flag>` for `<flag>`, and `<flag>` for `no-<flag>`.
+fn negation(long: &str) -> String {
+    match long.strip_prefix("no-") {
+        Some(flag) => flag.to_string(),
+        None => format!("no-{}", long),
+    }
+}
+
+/// Whether the option named by the config file's `key`, or its negation, was given on the
+/// command line.
+fn given(key: &str, prompt: &clap::Command, matches: &ArgMatches) -> bool {
+    let long = key.replace('_', "-");
+    [negation(&long), long].iter().any(|long| {
+        prompt
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+            .is_some_and(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+    })
+}
+
+fn default_path(dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter()
+        .find(|root| root.is_dir())
+        .map(|dir| dir.join(FILE_NAME))
+        .filter(|path| Path::is_file(path))
+}
+
+/// Converts `key = value` pairs to `--key=value` arguments. `true` becomes a bare flag and
+/// `false` nothing, and each item of a list is passed separately.
+fn to_arguments(table: &toml::Table) -> anyhow::Result<Vec<OsString>> {
+    let mut arguments = Vec::new();
+    for (key, value) in table {
+        let option = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            toml::Value::Array(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let argument = match value {
+                toml::Value::Boolean(true) => option.clone(),
+                toml::Value::Boolean(false) => continue,
+                toml::Value::String(text) => format!("{}={}", option, text),
+                toml::Value::Integer(number) => format!("{}={}", option, number),
+                toml::Value::Float(number) => format!("{}={}", option, number),
+                _ => anyhow::bail!("unsupported value for `{}`", key),
+            };
+            arguments.push(argument.into());
+        }
+    }
+    Ok(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_become_long_options() {
+        let table: toml::Table = toml::from_str(
+            "python = true\nrust = false\nremove-docs = [\"python\", \"js\"]\nmax_tokens = 100\nformat = \"xml\"\n",
+        )
+        .unwrap();
+        let mut arguments = to_arguments(&table).unwrap();
+        arguments.sort();
+        let expected = ["--format=xml", "--max-tokens=100", "--python", "--remove-docs=js", "--remove-docs=python"];
+        assert_eq!(arguments, expected.map(OsString::from));
+    }
+
+    #[test]
+    fn tables_are_rejected() {
+        let table: toml::Table = toml::from_str("[python]\nenabled = true\n").unwrap();
+        assert!(to_arguments(&table).unwrap_err().to_string().contains("`python`"));
+    }
+}
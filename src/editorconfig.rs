@@ -0,0 +1,60 @@
+//! `--respect-editorconfig`: re-indents unminified output with the tabs or spaces the
+//! project's `.editorconfig` asks for.
+//!
+//! Only leading whitespace is touched, line by line, so indentation inside multi-line
+//! string literals is converted too.
+
+use std::path::Path;
+
+use ec4rs::property::{IndentSize, IndentStyle, TabWidth};
+
+/// Columns per tab when `.editorconfig` doesn't say.
+const DEFAULT_WIDTH: usize = 4;
+
+/// Re-indents `content`, the text of `path`, per the `.editorconfig` files that apply to it.
+/// `None` when none of them sets `indent_style`.
+pub fn reindent(path: &Path, content: &str) -> Option<String> {
+    let mut properties = ec4rs::properties_of(path.canonicalize().ok()?).ok()?;
+    properties.use_fallbacks();
+    let style = properties.get::<IndentStyle>().ok()?;
+    let tab_width = match properties.get::<TabWidth>() {
+        Ok(TabWidth::Value(width)) if width > 0 => width,
+        _ => DEFAULT_WIDTH,
+    };
+    let indent_size = match properties.get::<IndentSize>() {
+        Ok(IndentSize::Value(size)) if size > 0 => size,
+        _ => tab_width,
+    };
+
+    let lines = content.split_inclusive('\n').map(|line| {
+        let body = line.trim_start_matches([' ', '\t']);
+        let columns = indent_columns(&line[..line.len() - body.len()], tab_width);
+        let indent = match style {
+            IndentStyle::Spaces => " ".repeat(columns),
+            IndentStyle::Tabs => format!("{}{}", "\t".repeat(columns / indent_size), " ".repeat(columns % indent_size)),
+        };
+        indent + body
+    });
+    Some(lines.collect())
+}
+
+/// Width of a run of tabs and spaces, with tab stops every `tab_width` columns.
+fn indent_columns(indent: &str, tab_width: usize) -> usize {
+    indent.chars().fold(0, |columns, c| match c {
+        '\t' => (columns / tab_width + 1) * tab_width,
+        _ => columns + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabs_advance_to_the_next_stop() {
+        assert_eq!(indent_columns("\t", 4), 4);
+        assert_eq!(indent_columns("  \t", 4), 4);
+        assert_eq!(indent_columns("\t  \t", 4), 8);
+        assert_eq!(indent_columns("\t ", 8), 9);
+    }
+}
@@ -0,0 +1,158 @@
+//! Just enough `.gitattributes` parsing to find files marked `linguist-generated` or
+//! `linguist-vendored`, which are usually machine-written or third-party code.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+
+struct Rule {
+    /// Directory holding the `.gitattributes` file; patterns are relative to it
+    dir: PathBuf,
+    pattern: GlobMatcher,
+    /// `Some(true)` marks matching files generated, `Some(false)` unmarks them
+    generated: Option<bool>,
+    vendored: Option<bool>,
+}
+
+pub struct GitAttributes {
+    /// Ordered so that later rules take precedence, as in git
+    rules: Vec<Rule>,
+}
+
+impl GitAttributes {
+    /// Loads the `.gitattributes` files that apply under `root`: those in `root` and its
+    /// ancestors up to the repository root, and any nested ones inside it.
+    pub fn load(root: &Path) -> Self {
+        let mut files = Vec::new();
+        if let Ok(root) = fs::canonicalize(root) {
+            for dir in root.ancestors() {
+                // `root` itself is found by the walk below
+                if dir != root {
+                    files.push(dir.join(".gitattributes"));
+                }
+                if dir.join(".git").exists() {
+                    break;
+                }
+            }
+            files.reverse();
+        }
+        // Shallower files come first so deeper ones override them
+        let mut nested: Vec<PathBuf> = WalkBuilder::new(root)
+            .hidden(false)
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() == ".gitattributes")
+            .filter_map(|entry| fs::canonicalize(entry.path()).ok())
+            .collect();
+        nested.sort_by_key(|path| path.components().count());
+        files.extend(nested);
+
+        let rules = files
+            .iter()
+            .filter_map(|file| Some((file.parent()?, fs::read_to_string(file).ok()?)))
+            .flat_map(|(dir, contents)| parse(dir, &contents))
+            .collect();
+        GitAttributes { rules }
+    }
+
+    /// True when the last matching rule marks `path` as generated or vendored.
+    pub fn is_generated(&self, path: &Path) -> bool {
+        let Ok(path) = fs::canonicalize(path) else {
+            return false;
+        };
+        let mut generated = false;
+        let mut vendored = false;
+        for rule in &self.rules {
+            let Ok(relative) = path.strip_prefix(&rule.dir) else {
+                continue;
+            };
+            if rule.pattern.is_match(relative) {
+                generated = rule.generated.unwrap_or(generated);
+                vendored = rule.vendored.unwrap_or(vendored);
+            }
+        }
+        generated || vendored
+    }
+}
+
+/// Parses the lines of one `.gitattributes` file that set a linguist attribute.
+fn parse(dir: &Path, contents: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    for line in contents.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next().filter(|pattern| !pattern.starts_with('#')) else {
+            continue;
+        };
+
+        let mut generated = None;
+        let mut vendored = None;
+        for attribute in fields {
+            let (name, value) = match attribute.split_once('=') {
+                Some((name, value)) => (name, value != "false"),
+                None => match attribute.strip_prefix(['-', '!']) {
+                    Some(name) => (name, false),
+                    None => (attribute, true),
+                },
+            };
+            match name {
+                "linguist-generated" => generated = Some(value),
+                "linguist-vendored" => vendored = Some(value),
+                _ => {}
+            }
+        }
+        if generated.is_none() && vendored.is_none() {
+            continue;
+        }
+
+        // Like .gitignore, a pattern without a slash matches at any depth
+        let pattern = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{}", pattern),
+        };
+        if let Ok(glob) = GlobBuilder::new(&pattern).literal_separator(true).build() {
+            rules.push(Rule {
+                dir: dir.to_path_buf(),
+                pattern: glob.compile_matcher(),
+                generated,
+                vendored,
+            });
+        }
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_rules_and_deeper_files_take_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("api/vendor/lib")).unwrap();
+        fs::write(
+            root.join(".gitattributes"),
+            "# generated code\n*.pb.go linguist-generated=true\nkeep.pb.go -linguist-generated\n/api/vendor/** linguist-vendored\n",
+        )
+        .unwrap();
+        fs::write(root.join("api/.gitattributes"), "local.pb.go linguist-generated=false\n").unwrap();
+        for file in ["a.pb.go", "keep.pb.go", "api/local.pb.go", "api/other.pb.go", "api/vendor/lib/x.go", "main.go"] {
+            fs::write(root.join(file), "package main\n").unwrap();
+        }
+
+        let attributes = GitAttributes::load(root);
+        assert!(attributes.is_generated(&root.join("a.pb.go")));
+        assert!(!attributes.is_generated(&root.join("keep.pb.go")));
+        assert!(!attributes.is_generated(&root.join("api/local.pb.go")));
+        assert!(attributes.is_generated(&root.join("api/other.pb.go")));
+        assert!(attributes.is_generated(&root.join("api/vendor/lib/x.go")));
+        assert!(!attributes.is_generated(&root.join("main.go")));
+
+        // Walking a subdirectory still applies the repository's rules
+        assert!(GitAttributes::load(&root.join("api")).is_generated(&root.join("api/other.pb.go")));
+    }
+}
@@ -1,25 +1,93 @@
-use clap::Parser;
+use anonymize::Anonymizer;
+use cache::Cache;
+use clap::{Parser, ValueEnum};
+use gitattributes::GitAttributes;
+use std::ffi::OsString;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use ignore::WalkBuilder;
+use indicatif::{ProgressBar, ProgressStyle};
 use rustminify::{remove_docs, minify_file};
+use sha2::{Digest, Sha256};
 use minify_js::{Session, TopLevelMode, minify};
+use tracing::{debug, info};
+
+mod anonymize;
+mod attributes;
+mod cache;
+mod config;
+mod editorconfig;
+mod gitattributes;
+#[cfg(feature = "interactive")]
+mod picker;
+mod redact;
+mod summary;
+mod symbols;
+mod template;
+mod test_files;
+mod typescript;
+mod yaml;
+
+// Cargo runs `cargo-prompt prompt ...` for `cargo prompt ...`, so the binary's own
+// command line is `cargo` with a single `prompt` subcommand
+#[derive(Parser)]
+#[command(name = "cargo", bin_name = "cargo")]
+enum CargoCli {
+    Prompt(Cli),
+}
+
+/// The program's arguments as cargo would pass them, with `prompt` added when the binary
+/// is run directly as `cargo-prompt ./dir`.
+fn command_line() -> Vec<OsString> {
+    let mut command_line: Vec<OsString> = std::env::args_os().collect();
+    if command_line.get(1).is_none_or(|first| first != "prompt") {
+        command_line.insert(1.min(command_line.len()), "prompt".into());
+    }
+    command_line
+}
 
 /// A small CLI application that traverses a directory for `.rs` files,
 /// optionally strips documentation, and minifies each file's contents.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(clap::Args, Debug)]
+// A repeated option replaces the earlier value rather than being an error
+#[command(author, version, about, long_about = None, args_override_self = true)]
 struct Cli {
-    #[arg(value_name = "cargo-command")]
-    command: String,
+    /// Directories to traverse and/or individual files to include
+    #[arg(default_value = ".", value_name = "PATH")]
+    dirs: Vec<PathBuf>,
+
+    /// Process exactly the files listed in FILE, one per line ("-" for stdin), instead of walking directories
+    #[arg(long = "files-from", value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Remove documentation before minifying, optionally only for the listed languages (e.g. --remove-docs=python,js)
+    #[arg(
+        short = 'r',
+        long = "remove-docs",
+        value_name = "LANGUAGES",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    remove_docs: Option<Vec<String>>,
 
-    /// Path to the directory to traverse
-    #[arg(default_value = ".", value_name = "DIR")]
-    dir: PathBuf,
+    /// Preserve original formatting instead of minifying
+    #[arg(long = "no-minify")]
+    no_minify: bool,
 
-    /// Remove documentation before minifying
-    #[arg(short = 'r', long = "remove-docs")]
-    remove_docs: bool,
+    /// How far to minify Rust files, overriding --no-minify for them
+    #[arg(long = "minify-level", value_enum, value_name = "LEVEL")]
+    minify_level: Option<MinifyLevel>,
+
+    /// Skip .rs files, which are otherwise always included
+    #[arg(long = "no-rust")]
+    no_rust: bool,
     
     /// Also minify .js files
     #[arg(short = 'j', long = "javascript")]
@@ -36,6 +104,10 @@ struct Cli {
     /// Also minify .c / .cpp files
     #[arg(short = 'c', long = "c-cpp")]
     cpp: bool,
+
+    /// Also minify Objective-C .m / .mm files, and .h headers unless -c or -a is given
+    #[arg(long = "objc")]
+    objc: bool,
     
     /// Also minify .csharp files
     #[arg(short = 'i', long = "csharp")]
@@ -101,7 +173,7 @@ struct Cli {
     #[arg(long = "haskell")]
     haskell: bool,
     
-    /// Also minify .sh files
+    /// Also minify .sh files and shell startup files such as .bashrc
     #[arg(long = "shell")]
     shell: bool,
     
@@ -109,768 +181,3789 @@ struct Cli {
     #[arg(short = 'l', long = "lua")]
     lua: bool,
     
+    /// Also minify .zig files
+    #[arg(long = "zig")]
+    zig: bool,
+    
+    /// Also minify .nim files
+    #[arg(long = "nim")]
+    nim: bool,
+    
+    /// Also minify .ex and .exs files
+    #[arg(long = "elixir")]
+    elixir: bool,
+    
+    /// Also minify PowerShell scripts (.ps1, .psm1, .psd1)
+    #[arg(long = "powershell")]
+    powershell: bool,
+    
+    /// Also minify .bat and .cmd files
+    #[arg(long = "batch")]
+    batch: bool,
+    
+    /// Also minify .sql files
+    #[arg(long = "sql")]
+    sql: bool,
+    
+    /// Also include .vue single-file components (not minified)
+    #[arg(long = "vue")]
+    vue: bool,
+    
+    /// Also include .svelte components (not minified)
+    #[arg(long = "svelte")]
+    svelte: bool,
+    
+    /// Also minify .proto files
+    #[arg(long = "proto")]
+    proto: bool,
+    
+    /// Also minify .graphql and .gql files
+    #[arg(long = "graphql")]
+    graphql: bool,
+    
+    /// Also minify .thrift files
+    #[arg(long = "thrift")]
+    thrift: bool,
+    
+    /// Also minify .ml and .mli files
+    #[arg(long = "ocaml")]
+    ocaml: bool,
+    
+    /// Also minify .fs, .fsx, and .fsi files
+    #[arg(long = "fsharp")]
+    fsharp: bool,
+    
+    /// Also include Clojure files (.clj, .cljs, .cljc, .edn, not minified)
+    #[arg(long = "clojure")]
+    clojure: bool,
+    
+    /// Also minify assembly (.asm and .nasm with `;` comments, .s and .S with GAS `#` comments)
+    #[arg(long = "asm")]
+    asm: bool,
+    
+    /// Also minify .erl and .hrl files
+    #[arg(long = "erlang")]
+    erlang: bool,
+    
+    /// Also include Lisp and Scheme files (.lisp, .el, .scm, .rkt, ..., not minified)
+    #[arg(long = "lisp")]
+    lisp: bool,
+    
+    /// Also include Fortran files (.f90 and newer minified line by line, fixed-form .f / .for as written)
+    #[arg(long = "fortran")]
+    fortran: bool,
+    
+    /// Also minify .pas and .dpr files
+    #[arg(long = "pascal")]
+    pascal: bool,
+    
+    /// Also include COBOL files (.cob, .cbl, .cpy, not minified)
+    #[arg(long = "cobol")]
+    cobol: bool,
+    
+    /// Also minify CUDA files (.cu, .cuh)
+    #[arg(long = "cuda")]
+    cuda: bool,
+    
+    /// Also minify GLSL shaders (.glsl, .vert, .frag, .comp, ...)
+    #[arg(long = "glsl")]
+    glsl: bool,
+    
+    /// Also minify HLSL shaders (.hlsl, .fx)
+    #[arg(long = "hlsl")]
+    hlsl: bool,
+    
+    /// Also minify Dockerfiles
+    #[arg(long = "docker")]
+    docker: bool,
+    
+    /// Also minify Makefiles, .mk files, CMakeLists.txt, and .cmake files
+    #[arg(long = "make")]
+    make: bool,
+    
     /// Minify all supported languages
     #[arg(short = 'a', long = "all")]
     all: bool,
+
+    /// List each file's top-level symbols above its code
+    #[arg(long = "symbols")]
+    symbols: bool,
+
+    /// List the public items of each Rust file above its code, or in place of it with --outline=only
+    #[arg(
+        long = "outline",
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "before",
+        conflicts_with = "symbols"
+    )]
+    outline: Option<Outline>,
+
+    /// Start with a list of every file, linked to its section
+    #[arg(long = "toc", conflicts_with = "chunk")]
+    toc: bool,
+
+    /// Render the prompt with this TinyTemplate file instead of --format, e.g. "# {project}" then a {{ for file in files }} loop
+    #[arg(
+        long = "template",
+        value_name = "FILE",
+        conflicts_with_all = ["format", "chunk", "split_by_dir", "toc", "clipboard", "list", "count_only"]
+    )]
+    template: Option<PathBuf>,
+
+    /// Put each file's path on its opening code fence (```rust src/main.rs) instead of a heading above it
+    #[arg(long = "compact", visible_alias = "compact-headers", conflicts_with = "toc")]
+    compact: bool,
+
+    /// Only include files changed on this branch since it diverged from BASE
+    #[arg(long = "branch-changes", value_name = "BASE", num_args = 0..=1, default_missing_value = "main")]
+    branch_changes: Option<String>,
+
+    /// Only include files that differ from REF (a commit, tag, or branch) in the working tree
+    #[arg(long = "changed-since", value_name = "REF", conflicts_with = "branch_changes")]
+    changed_since: Option<String>,
+
+    /// Only include files modified within this long before now, e.g. 24h, 7d, or "1h 30m"
+    #[arg(long = "modified-within", value_name = "DURATION", value_parser = humantime::parse_duration)]
+    modified_within: Option<std::time::Duration>,
+
+    /// Leave out test files, recognized by names like *_test.go, test_*.py, and *.spec.ts or a tests/ directory
+    #[arg(long = "no-tests", conflicts_with = "only_tests")]
+    no_tests: bool,
+
+    /// Only include test files, as recognized by --no-tests
+    #[arg(long = "only-tests")]
+    only_tests: bool,
+
+    /// Approximate token budget; whole files are omitted once it would be exceeded
+    #[arg(long = "max-tokens", value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// Use this model's context window (e.g. gpt-4o, claude-sonnet-4, gemini-2.5-pro) as the --max-tokens budget
+    #[arg(long = "model", value_name = "NAME", value_parser = parse_model)]
+    model: Option<(&'static str, usize)>,
+
+    /// With --max-tokens, split the output into PREFIX.part1.md, PREFIX.part2.md, ... instead of omitting files
+    #[arg(long = "chunk", value_name = "PREFIX", requires = "max_tokens", num_args = 0..=1, default_missing_value = "prompt")]
+    chunk: Option<String>,
+
+    /// Write one prompt per top-level directory into DIR instead, e.g. DIR/src-prompt.md and DIR/tests-prompt.md
+    #[arg(long = "split-by-dir", value_name = "DIR", conflicts_with_all = ["chunk", "output", "clipboard", "toc"])]
+    split_by_dir: Option<PathBuf>,
+
+    /// Replace files larger than N bytes with a structural summary (imports, declarations, first lines)
+    #[arg(long = "summarize-large", value_name = "N")]
+    summarize_large: Option<u64>,
+
+    /// Summarize edition and dependencies from Cargo.toml under the project header
+    #[arg(long = "with-deps")]
+    with_deps: bool,
+
+    /// Include hidden files and directories (dotfiles); can pull in a lot of noise
+    #[arg(long = "hidden")]
+    hidden: bool,
+
+    /// Don't filter files using .gitignore, .ignore, or .git/info/exclude
+    #[arg(long = "no-gitignore")]
+    no_gitignore: bool,
+
+    /// Maximum directory depth to descend; 1 means only files directly inside each directory
+    #[arg(long = "depth")]
+    depth: Option<usize>,
+
+    /// Mask likely secrets (API keys, bearer tokens, passwords) in the output
+    #[arg(long = "redact")]
+    redact: bool,
+
+    /// Don't append the summary footer with file counts, sizes, and token estimate
+    #[arg(long = "no-stats")]
+    no_stats: bool,
+
+    /// Keep a license / copyright comment at the top of each file intact
+    #[arg(long = "keep-license")]
+    keep_license: bool,
+
+    /// With --no-minify, collapse runs of blank lines into a single blank line
+    #[arg(long = "collapse-blank-lines", requires = "no_minify")]
+    collapse_blank_lines: bool,
+
+    /// Include the code cells of Jupyter notebooks (.ipynb) as Python
+    #[arg(long = "notebook")]
+    notebook: bool,
+
+    /// Only list the files that would be included, with their language, without processing them
+    #[arg(long = "list", visible_alias = "dry-run")]
+    list: bool,
+
+    /// Show file paths under this base instead of relative to the walked directory
+    #[arg(long = "path-prefix", value_name = "PREFIX")]
+    path_prefix: Option<String>,
+
+    /// Show only each file's name in its header, numbering repeated names
+    #[arg(long, conflicts_with = "path_prefix")]
+    flatten: bool,
+
+    /// Also include .toml, .yaml, and .json config files (except Cargo.toml)
+    #[arg(long = "configs")]
+    configs: bool,
+
+    /// Also include .yaml and .yml files, keeping their indentation and document separators
+    #[arg(long = "yaml")]
+    yaml: bool,
+
+    /// Also include .ini, .properties, and .env files, masking likely secrets in .env files
+    #[arg(long = "dotconfig")]
+    dotconfig: bool,
+
+    /// Show a running count of processed files on stderr
+    #[arg(long = "progress")]
+    progress: bool,
+
+    /// Process every file again instead of reusing results cached by earlier runs
+    #[arg(long = "no-cache")]
+    no_cache: bool,
+
+    /// Copy the prompt to the system clipboard instead of printing it (needs the `clipboard` feature)
+    #[arg(long = "clipboard")]
+    clipboard: bool,
+
+    /// Include every text file exactly as written, skipping all language processing
+    #[arg(long = "concat")]
+    concat: bool,
+
+    /// With --no-minify, prefix each line with its line number in the original file
+    #[arg(
+        long = "line-numbers",
+        requires = "no_minify",
+        // Each of these adds or removes lines, so the numbers would no longer match the file
+        conflicts_with_all = [
+            "collapse_blank_lines",
+            "remove_docs",
+            "strip_attributes",
+            "trim_imports",
+            "anonymize",
+            "head",
+            "tail",
+            "filter_cmd",
+        ]
+    )]
+    line_numbers: bool,
+
+    /// Only include the first N lines of longer files (before minification), noting how many were left out
+    #[arg(long = "head", value_name = "N")]
+    head: Option<usize>,
+
+    /// Only include the last N lines of longer files (before minification), noting how many were left out
+    #[arg(long = "tail", value_name = "N")]
+    tail: Option<usize>,
+
+    /// Replace each file's processed content with the output of this shell command, which reads it on stdin
+    #[arg(long = "filter-cmd", value_name = "COMMAND")]
+    filter_cmd: Option<String>,
+
+    /// Log each file processed or skipped (and why) and how long each step took to stderr; repeat for more
+    // `-v` is taken by `--vbnet`
+    #[arg(long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Also write a JSON manifest of each included file's path and SHA-256, with the tool version and arguments, to FILE
+    #[arg(long = "manifest", value_name = "FILE", conflicts_with = "list")]
+    manifest: Option<PathBuf>,
+
+    /// Wrap lines longer than N columns at whitespace outside string literals, marking each continuation with a comment
+    #[arg(long = "wrap", value_name = "N", requires = "no_minify", conflicts_with = "line_numbers")]
+    wrap: Option<usize>,
+
+    /// Convert indentation to the tabs or spaces set in .editorconfig, for code that isn't minified
+    #[arg(long = "respect-editorconfig")]
+    respect_editorconfig: bool,
+
+    /// Only include lines matching REGEX (e.g. "// PROMPT|TODO"), with --context lines around each; files without a match are left out
+    #[arg(long = "marker", value_name = "REGEX", conflicts_with = "line_numbers")]
+    marker: Option<regex::Regex>,
+
+    /// Lines of context to keep before and after each --marker line
+    #[arg(long = "context", value_name = "N", default_value_t = 0, requires = "marker")]
+    context: usize,
+
+    /// Group files under a heading per language instead of walk order
+    #[arg(long = "group-by", value_enum, value_name = "KEY")]
+    group_by: Option<GroupBy>,
+
+    /// Skip files with this extension even if their language is enabled (repeatable)
+    #[arg(long = "exclude-ext", value_name = "EXT")]
+    exclude_ext: Vec<String>,
+
+    /// Only process files with this extension, whatever the language flags say (repeatable)
+    #[arg(long = "only-ext", value_name = "EXT")]
+    only_ext: Vec<String>,
+
+    /// Process files with extension EXT as LANGUAGE, whatever the language flags say, e.g. --map-ext inc=php (repeatable)
+    #[arg(long = "map-ext", value_name = "EXT=LANGUAGE", value_parser = parse_extension_mapping)]
+    map_ext: Vec<(String, &'static str)>,
+
+    /// Show each file's size and modification date in its header
+    #[arg(long = "header-meta")]
+    header_meta: bool,
+
+    /// Use this as the top-level heading instead of the Cargo package name
+    #[arg(long = "title")]
+    title: Option<String>,
+
+    /// Instructions placed above everything else, so the output is ready to send as-is
+    #[arg(long = "prelude", value_name = "TEXT", conflicts_with = "prelude_file")]
+    prelude: Option<String>,
+
+    /// Like --prelude, but read the instructions from a file
+    #[arg(long = "prelude-file", value_name = "PATH")]
+    prelude_file: Option<PathBuf>,
+
+    /// Annotate each file with the line and token counts of its emitted content
+    #[arg(long = "per-file-stats")]
+    per_file_stats: bool,
+
+    /// Include files marked linguist-generated or linguist-vendored in .gitattributes
+    #[arg(long = "include-generated")]
+    include_generated: bool,
+
+    /// Replace files whose output repeats an earlier file's with a note naming that file
+    #[arg(long)]
+    dedupe: bool,
+
+    /// Read default options from this TOML file instead of the project's .cargo-prompt.toml
+    #[arg(long = "config", value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Follow symbolic links to directories and files while walking
+    #[arg(long = "follow-symlinks")]
+    follow_symlinks: bool,
+
+    /// Stop after this many matching files, as a guard against walking a huge tree by mistake
+    #[arg(long = "max-files", value_name = "N", default_value_t = 10_000)]
+    max_files: usize,
+
+    /// Rename Rust functions, types, and variables to placeholders like `fn_1` and `var_3`
+    #[arg(long, conflicts_with_all = ["symbols", "outline", "summarize_large"])]
+    anonymize: bool,
+
+    /// Remove attributes such as #[derive(..)] from Rust items, optionally only the listed ones (e.g. --strip-attributes=derive,serde)
+    #[arg(
+        long = "strip-attributes",
+        value_name = "PATHS",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    strip_attributes: Option<Vec<String>>,
+
+    /// Show the crate's root file with every macro expanded (via `cargo expand`) in place of its Rust files
+    #[arg(long = "expand-macros", conflicts_with_all = ["files_from", "summarize_large"])]
+    expand_macros: bool,
+
+    /// Drop top-level `use` declarations from Rust files
+    #[arg(long = "trim-imports")]
+    trim_imports: bool,
+
+    /// Report each file's ratio of comment to code characters on stderr, least documented first
+    #[arg(long = "comment-report", visible_alias = "comment-density")]
+    comment_report: bool,
+
+    /// Print a table of files, lines, blank lines, and comment lines per language instead of the prompt
+    #[arg(long = "count-only", conflicts_with_all = ["list", "manifest", "clipboard"])]
+    count_only: bool,
+
+    /// Choose the files to include in a terminal UI, with a running token estimate, before writing the prompt
+    #[arg(long = "interactive", conflicts_with_all = ["list", "count_only"])]
+    interactive: bool,
+
+    /// Output format: markdown code fences, or XML `<file>` elements for models that prefer tags
+    #[arg(long, value_enum, default_value = "markdown", conflicts_with_all = ["chunk", "split_by_dir"])]
+    format: Format,
+
+    /// Write the prompt to this file instead of stdout, compressed if it ends in .gz or .zst
+    #[arg(short = 'o', long, value_name = "FILE", conflicts_with_all = ["chunk", "clipboard"])]
+    output: Option<PathBuf>,
+
+    /// Compress the --output file regardless of its extension
+    #[arg(long, value_enum, requires = "output")]
+    compress: Option<Compression>,
+
+    /// How to read files that aren't valid UTF-8
+    #[arg(long, value_enum, value_name = "ENCODING", default_value = "lossy")]
+    encoding: Encoding,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum GroupBy {
+    /// One heading per language, files sorted by path within each
+    Language,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Markdown,
+    /// `<project>` wrapping one `<file path=".." lang="..">` element per file
+    Xml,
+    /// One `{"path": .., "language": .., "content": ..}` object per line, and nothing else
+    Jsonl,
+}
+
+impl Format {
+    /// Presents a block of markdown text such as the stats footer: as is, or escaped inside
+    /// a `<tag>` element. Empty text stays empty.
+    fn block(self, tag: &str, text: &str) -> String {
+        match self {
+            _ if text.is_empty() => String::new(),
+            Format::Markdown => text.to_string(),
+            Format::Xml => format!("<{}>\n{}\n</{}>\n", tag, xml_text(text.trim_end()), tag),
+            // Every line has to be a file record
+            Format::Jsonl => String::new(),
+        }
+    }
+
+    /// Whatever closes the document after the last file and footer.
+    fn closing(self) -> &'static str {
+        match self {
+            Format::Markdown => "\n",
+            Format::Xml => "</project>\n\n",
+            Format::Jsonl => "",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+enum MinifyLevel {
+    /// The original source, unless another option has to rewrite it
+    None,
+    /// Reformatted with comments and blank lines removed
+    Light,
+    /// Everything on one line
+    Full,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+enum Outline {
+    /// The outline followed by the code
+    Before,
+    /// Just the outline
+    Only,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+#[derive(Clone, Copy, Debug, Hash, ValueEnum)]
+enum Encoding {
+    /// Skip the file
+    Utf8,
+    /// Replace invalid bytes with U+FFFD
+    Lossy,
+    /// Decode the file as Latin-1 (Windows-1252)
+    Latin1,
+}
+
+impl Cli {
+    /// How Rust files are minified: `--minify-level`, or whatever `--no-minify` implies.
+    fn rust_minify_level(&self) -> MinifyLevel {
+        match self.minify_level {
+            Some(level) => level,
+            None if self.no_minify => MinifyLevel::None,
+            None => MinifyLevel::Full,
+        }
+    }
+
+    /// Whether documentation should be removed from files of `language`.
+    fn strips_docs(&self, language: &str) -> bool {
+        match &self.remove_docs {
+            None => false,
+            Some(languages) if languages.is_empty() => true,
+            Some(languages) => languages
+                .iter()
+                .any(|name| canonical_language(name) == Some(language)),
+        }
+    }
+}
+
+/// Resolves a user-supplied language name or common alias to a built-in language name.
+fn canonical_language(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_lowercase();
+    let canonical = match name.as_str() {
+        "rs" => "rust",
+        "js" => "javascript",
+        "py" => "python",
+        "c" | "c++" | "h" => "cpp",
+        "objective-c" | "objectivec" | "obj-c" => "objc",
+        "cs" | "c#" => "csharp",
+        "rb" => "ruby",
+        "ts" => "typescript",
+        "kt" => "kotlin",
+        "golang" => "go",
+        "vb" => "vbnet",
+        "pl" => "perl",
+        "jl" => "julia",
+        "hs" => "haskell",
+        "sh" | "shell" => "bash",
+        "ex" | "exs" => "elixir",
+        "ps1" | "pwsh" => "powershell",
+        "bat" | "cmd" => "batch",
+        "proto" => "protobuf",
+        "gql" => "graphql",
+        "ml" => "ocaml",
+        "f#" | "fs" => "fsharp",
+        "clj" => "clojure",
+        "nasm" => "asm",
+        "erl" => "erlang",
+        "scheme" | "elisp" => "lisp",
+        "f90" => "fortran",
+        "pas" | "delphi" => "pascal",
+        "cob" | "cbl" => "cobol",
+        "cu" => "cuda",
+        "docker" => "dockerfile",
+        "makefile" => "make",
+        "env" | ".env" => "dotenv",
+        other => other,
+    };
+    ["rust", "javascript"]
+        .into_iter()
+        .chain(LANGUAGES.iter().chain(CONFIG_FORMATS).map(|spec| spec.name))
+        .find(|known| *known == canonical)
+}
+
+/// Comment syntax and file matching for a language handled by the generic pipeline.
+struct LanguageSpec {
+    /// Short name used to look up language-specific tooling
+    name: &'static str,
+    /// Label placed after the opening code fence
+    fence: &'static str,
+    /// Returns true when the language's own CLI flag is set
+    enabled: fn(&Cli) -> bool,
+    extensions: &'static [&'static str],
+    /// Exact file names matched regardless of extension, e.g. `Dockerfile`
+    file_names: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    /// `(start, end)` delimiters of each kind of block comment
+    block_comments: &'static [(&'static str, &'static str)],
+    /// Block comments may contain other block comments
+    nested_block_comments: bool,
+    /// Whole-line comments marked by one of these characters in a fixed (1-based) column,
+    /// as in fixed-form Fortran and COBOL
+    column_comments: Option<(usize, &'static str)>,
+    /// Whitespace can be removed without changing meaning
+    minify: bool,
+    /// Newlines end statements, so minifying keeps one statement per line
+    preserve_newlines: bool,
+    /// JavaScript's backtick template strings and `/.../` regex literals, which minifying
+    /// copies verbatim
+    js_literals: bool,
+    _default_skip_dirs: &'static [&'static str],
+}
+
+impl LanguageSpec {
+    fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| self.extensions.contains(&ext))
+            || path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .is_some_and(|name| self.file_names.contains(&name))
+    }
 }
 
+/// Every language processed by `remove_documentation` + `remove_whitespace`.
+/// Rust and JavaScript have dedicated minifiers and are handled separately.
+const LANGUAGES: &[LanguageSpec] = &[
+    LanguageSpec {
+        name: "python",
+        fence: "python",
+        enabled: |args| args.python,
+        extensions: &["py", "pyw"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("'''", "'''")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["__pycache__", "venv", ".env", "dist"],
+    },
+    LanguageSpec {
+        name: "java",
+        fence: "java",
+        enabled: |args| args.java,
+        extensions: &["java"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["target", "build", "out"],
+    },
+    LanguageSpec {
+        name: "cpp",
+        fence: "cpp",
+        enabled: |args| args.cpp,
+        extensions: &["cpp", "hpp", "cc", "hh", "cxx", "hxx", "c", "h"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["build", "obj", "bin"],
+    },
+    // `#import` and other preprocessor lines must stay on their own line. `.m` and `.h` are
+    // shared with MATLAB and C, see `defers_to_other_language`.
+    LanguageSpec {
+        name: "objc",
+        fence: "objectivec",
+        enabled: |args| args.objc,
+        extensions: &["m", "mm", "h"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[".build", "DerivedData", "Pods"],
+    },
+    LanguageSpec {
+        name: "csharp",
+        fence: "csharp",
+        enabled: |args| args.csharp,
+        extensions: &["cs"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["bin", "obj", "Debug", "Release"],
+    },
+    LanguageSpec {
+        name: "php",
+        fence: "php",
+        enabled: |args| args.php,
+        extensions: &["php"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["vendor", "cache"],
+    },
+    LanguageSpec {
+        name: "ruby",
+        fence: "ruby",
+        enabled: |args| args.ruby,
+        extensions: &["rb"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("=begin", "=end")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["vendor", "tmp", "log"],
+    },
+    LanguageSpec {
+        name: "swift",
+        fence: "swift",
+        enabled: |args| args.swift,
+        extensions: &["swift"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[".build", "Pods"],
+    },
+    LanguageSpec {
+        name: "typescript",
+        fence: "typescript",
+        enabled: |args| args.typescript,
+        extensions: &["ts", "tsx"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: true,
+        _default_skip_dirs: &["node_modules", "dist", "build"],
+    },
+    LanguageSpec {
+        name: "kotlin",
+        fence: "kotlin",
+        enabled: |args| args.kotlin,
+        extensions: &["kt", "kts"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["build", "out"],
+    },
+    LanguageSpec {
+        name: "go",
+        fence: "go",
+        enabled: |args| args.go,
+        extensions: &["go"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["vendor", "bin"],
+    },
+    // R doesn't truly have traditional block comments
+    LanguageSpec {
+        name: "r",
+        fence: "r",
+        enabled: |args| args.r,
+        extensions: &["r", "R"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["renv"],
+    },
+    LanguageSpec {
+        name: "matlab",
+        fence: "matlab",
+        enabled: |args| args.matlab,
+        extensions: &["m"],
+        file_names: &[],
+        line_comments: &["%"],
+        block_comments: &[("%{", "%}")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["bin"],
+    },
+    // VB.NET uses line comments primarily
+    LanguageSpec {
+        name: "vbnet",
+        fence: "vbnet",
+        enabled: |args| args.vbnet,
+        extensions: &["vb"],
+        file_names: &[],
+        line_comments: &["'"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["bin", "obj"],
+    },
+    LanguageSpec {
+        name: "scala",
+        fence: "scala",
+        enabled: |args| args.scala,
+        extensions: &["scala"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["target", "project/target"],
+    },
+    LanguageSpec {
+        name: "perl",
+        fence: "perl",
+        enabled: |args| args.perl,
+        extensions: &["pl", "pm"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("=pod", "=cut")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["blib", "_build"],
+    },
+    LanguageSpec {
+        name: "dart",
+        fence: "dart",
+        enabled: |args| args.dart,
+        extensions: &["dart"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["build", ".dart_tool"],
+    },
+    LanguageSpec {
+        name: "groovy",
+        fence: "groovy",
+        enabled: |args| args.groovy,
+        extensions: &["groovy", "gvy", "gy", "gsh"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["target", "build"],
+    },
+    LanguageSpec {
+        name: "julia",
+        fence: "julia",
+        enabled: |args| args.julia,
+        extensions: &["jl"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("#=", "=#")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["docs/build"],
+    },
+    LanguageSpec {
+        name: "haskell",
+        fence: "haskell",
+        enabled: |args| args.haskell,
+        extensions: &["hs", "lhs"],
+        file_names: &[],
+        line_comments: &["--"],
+        block_comments: &[("{-", "-}")],
+        nested_block_comments: true,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["dist", ".stack-work"],
+    },
+    // Shell typically uses only line comments
+    LanguageSpec {
+        name: "bash",
+        fence: "bash",
+        enabled: |args| args.shell,
+        extensions: &["sh", "bash"],
+        file_names: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &["tmp"],
+    },
+    LanguageSpec {
+        name: "lua",
+        fence: "lua",
+        enabled: |args| args.lua,
+        extensions: &["lua"],
+        file_names: &[],
+        line_comments: &["--"],
+        block_comments: &[("--[[", "]]")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["bin"],
+    },
+    LanguageSpec {
+        name: "zig",
+        fence: "zig",
+        enabled: |args| args.zig,
+        extensions: &["zig"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["zig-cache", ".zig-cache", "zig-out"],
+    },
+    LanguageSpec {
+        name: "nim",
+        fence: "nim",
+        enabled: |args| args.nim,
+        extensions: &["nim", "nims", "nimble"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("#[", "]#")],
+        nested_block_comments: true,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["nimcache"],
+    },
+    LanguageSpec {
+        name: "elixir",
+        fence: "elixir",
+        enabled: |args| args.elixir,
+        extensions: &["ex", "exs"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["_build", "deps"],
+    },
+    LanguageSpec {
+        name: "powershell",
+        fence: "powershell",
+        enabled: |args| args.powershell,
+        extensions: &["ps1", "psm1", "psd1"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("<#", "#>")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "batch",
+        fence: "batch",
+        enabled: |args| args.batch,
+        extensions: &["bat", "cmd"],
+        file_names: &[],
+        line_comments: &["REM ", "rem ", "Rem ", "@REM ", "@rem ", "::"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "sql",
+        fence: "sql",
+        enabled: |args| args.sql,
+        extensions: &["sql"],
+        file_names: &[],
+        // `#` comments are MySQL-specific
+        line_comments: &["--", "#"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "vue",
+        fence: "vue",
+        enabled: |args| args.vue,
+        extensions: &["vue"],
+        file_names: &[],
+        // Markup, scripts, and styles mixed in one file, so only HTML comments are safe to strip
+        line_comments: &[],
+        block_comments: &[("<!--", "-->")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: true,
+        _default_skip_dirs: &["node_modules", "dist"],
+    },
+    LanguageSpec {
+        name: "svelte",
+        fence: "svelte",
+        enabled: |args| args.svelte,
+        extensions: &["svelte"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[("<!--", "-->")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: true,
+        _default_skip_dirs: &["node_modules", "dist"],
+    },
+    LanguageSpec {
+        name: "protobuf",
+        fence: "protobuf",
+        enabled: |args| args.proto,
+        extensions: &["proto"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "graphql",
+        fence: "graphql",
+        enabled: |args| args.graphql,
+        extensions: &["graphql", "gql"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "thrift",
+        fence: "thrift",
+        enabled: |args| args.thrift,
+        extensions: &["thrift"],
+        file_names: &[],
+        line_comments: &["//", "#"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "ocaml",
+        fence: "ocaml",
+        enabled: |args| args.ocaml,
+        extensions: &["ml", "mli"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[("(*", "*)")],
+        nested_block_comments: true,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["_build", "_opam"],
+    },
+    LanguageSpec {
+        name: "fsharp",
+        fence: "fsharp",
+        enabled: |args| args.fsharp,
+        extensions: &["fs", "fsx", "fsi"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("(*", "*)")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["bin", "obj"],
+    },
+    LanguageSpec {
+        name: "clojure",
+        fence: "clojure",
+        enabled: |args| args.clojure,
+        extensions: &["clj", "cljs", "cljc", "edn"],
+        file_names: &[],
+        line_comments: &[";"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        // Forms are separated only by whitespace
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["target", ".cpcache"],
+    },
+    LanguageSpec {
+        name: "asm",
+        fence: "asm",
+        enabled: |args| args.asm,
+        extensions: &["asm", "nasm"],
+        file_names: &[],
+        line_comments: &[";"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "gas",
+        fence: "asm",
+        enabled: |args| args.asm,
+        extensions: &["s", "S"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "erlang",
+        fence: "erlang",
+        enabled: |args| args.erlang,
+        extensions: &["erl", "hrl"],
+        file_names: &[],
+        line_comments: &["%"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &["_build", "ebin"],
+    },
+    LanguageSpec {
+        name: "lisp",
+        fence: "lisp",
+        enabled: |args| args.lisp,
+        extensions: &["lisp", "lsp", "el", "scm", "ss", "rkt"],
+        file_names: &[],
+        line_comments: &[";"],
+        block_comments: &[("#|", "|#")],
+        nested_block_comments: true,
+        column_comments: None,
+        // Like Clojure, forms are separated only by whitespace
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "fortran",
+        fence: "fortran",
+        enabled: |args| args.fortran,
+        extensions: &["f90", "f95", "f03", "f08", "F90"],
+        file_names: &[],
+        line_comments: &["!"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    // Fixed-form Fortran, where columns 1-6 hold labels and continuation marks
+    LanguageSpec {
+        name: "fortran",
+        fence: "fortran",
+        enabled: |args| args.fortran,
+        extensions: &["f", "for", "f77", "F", "FOR"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: Some((1, "Cc*!")),
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "pascal",
+        fence: "pascal",
+        enabled: |args| args.pascal,
+        extensions: &["pas", "dpr"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("{", "}"), ("(*", "*)")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        // Keywords like `begin` and `end` need the whitespace around them
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    // Columns 1-6 are a sequence area and column 7 an indicator, so layout is kept as is
+    LanguageSpec {
+        name: "cobol",
+        fence: "cobol",
+        enabled: |args| args.cobol,
+        extensions: &["cob", "cbl", "cpy", "COB", "CBL", "CPY"],
+        file_names: &[],
+        line_comments: &["*>"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: Some((7, "*/")),
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    // In CUDA and shader sources, preprocessor lines like `#include` and `#version` must stay
+    // on their own line
+    LanguageSpec {
+        name: "cuda",
+        fence: "cuda",
+        enabled: |args| args.cuda,
+        extensions: &["cu", "cuh"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &["build"],
+    },
+    LanguageSpec {
+        name: "glsl",
+        fence: "glsl",
+        enabled: |args| args.glsl,
+        extensions: &["glsl", "vert", "frag", "geom", "tesc", "tese", "comp"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "hlsl",
+        fence: "hlsl",
+        enabled: |args| args.hlsl,
+        extensions: &["hlsl", "hlsli", "fx"],
+        file_names: &[],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "dockerfile",
+        fence: "dockerfile",
+        enabled: |args| args.docker,
+        extensions: &["dockerfile"],
+        file_names: &["Dockerfile", "Containerfile"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "make",
+        fence: "makefile",
+        enabled: |args| args.make,
+        extensions: &["mk"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        // Recipe lines also keep their leading tab
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "cmake",
+        fence: "cmake",
+        enabled: |args| args.make,
+        extensions: &["cmake"],
+        file_names: &["CMakeLists.txt"],
+        line_comments: &["#"],
+        block_comments: &[("#[[", "]]")],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: true,
+        preserve_newlines: true,
+        js_literals: false,
+        _default_skip_dirs: &["build"],
+    },
+];
+
+/// Configuration formats included with `--configs` and `--dotconfig`. Their structure
+/// matters, so they're never minified, only stripped of comments with `-r` (YAML also has
+/// runs of blank lines squeezed, by `yaml::minify`).
+const CONFIG_FORMATS: &[LanguageSpec] = &[
+    LanguageSpec {
+        name: "toml",
+        fence: "toml",
+        enabled: |args| args.configs,
+        extensions: &["toml"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "yaml",
+        fence: "yaml",
+        enabled: |args| args.configs || args.yaml,
+        extensions: &["yaml", "yml"],
+        file_names: &[],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "json",
+        fence: "json",
+        enabled: |args| args.configs,
+        extensions: &["json"],
+        file_names: &[],
+        line_comments: &[],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "ini",
+        fence: "ini",
+        enabled: |args| args.dotconfig,
+        extensions: &["ini", "cfg"],
+        file_names: &[],
+        line_comments: &[";", "#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "properties",
+        fence: "properties",
+        enabled: |args| args.dotconfig,
+        extensions: &["properties"],
+        file_names: &[],
+        line_comments: &["#", "!"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+    LanguageSpec {
+        name: "dotenv",
+        fence: "dotenv",
+        enabled: |args| args.dotconfig,
+        extensions: &["env"],
+        file_names: &[".env", ".env.local", ".env.development", ".env.production", ".env.test", ".env.example"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_block_comments: false,
+        column_comments: None,
+        minify: false,
+        preserve_newlines: false,
+        js_literals: false,
+        _default_skip_dirs: &[],
+    },
+];
+
 fn main() -> anyhow::Result<()> {
-    let args = Cli::parse();
+    let args = config::parse(&command_line()).unwrap_or_else(|e| e.exit());
+    let mut args = config::apply(args)?;
+    // An explicit --max-tokens takes precedence
+    if let Some((_, window)) = args.model {
+        args.max_tokens.get_or_insert(window);
+    }
+    if args.verbose > 0 {
+        let level = match args.verbose {
+            1 => tracing::Level::INFO,
+            2 => tracing::Level::DEBUG,
+            _ => tracing::Level::TRACE,
+        };
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_max_level(level)
+            .with_ansi(std::io::stderr().is_terminal())
+            .without_time()
+            .with_target(false)
+            .init();
+    }
+    match run(args) {
+        // The reader went away (e.g. `cargo prompt | head`), so there's no one left to tell
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) => {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn run(args: Cli) -> anyhow::Result<()> {
+
+    for name in args.remove_docs.iter().flatten() {
+        if canonical_language(name).is_none() {
+            anyhow::bail!("unknown language `{}` in --remove-docs", name);
+        }
+    }
+    if args.files_from.is_none()
+        && let Some(missing) = args.dirs.iter().find(|dir| !dir.exists())
+    {
+        // A lone word where cargo puts the subcommand is most likely a mistyped one
+        if std::env::args_os().nth(1).is_some_and(|first| first == missing.as_os_str())
+            && missing.components().count() == 1
+            && missing.extension().is_none()
+        {
+            anyhow::bail!(
+                "{} does not exist (the subcommand is `prompt`, e.g. `cargo prompt ./src` or `cargo-prompt ./src`)",
+                missing.display()
+            );
+        }
+        anyhow::bail!("{} does not exist", missing.display());
+    }
+    if args.toc && args.format != Format::Markdown {
+        anyhow::bail!("--toc only works with markdown output");
+    }
     
-    // Attempt to load the project name from Cargo.toml
-    let cargo_toml_path = args.dir.join("Cargo.toml");
-    let project_name = if cargo_toml_path.exists() {
-        let contents = fs::read_to_string(&cargo_toml_path)?;
-        let parsed: toml::Value = toml::from_str(&contents)?;
-        // Grab the name from [package] table or default if missing
-        parsed
-            .get("package")
-            .and_then(|pkg| pkg.get("name"))
-            .and_then(|name| name.as_str())
-            .unwrap_or("Unnamed Project")
-            .to_owned()
+    // Attempt to load the project name from the first directory's Cargo.toml
+    let cargo_toml_path = args
+        .dirs
+        .iter()
+        .find(|root| root.is_dir())
+        .map(|dir| dir.join("Cargo.toml"))
+        .filter(|path| path.exists());
+    let manifest: Option<toml::Value> = match &cargo_toml_path {
+        Some(cargo_toml_path) => {
+            let contents = fs::read_to_string(cargo_toml_path)?;
+            Some(toml::from_str(&contents)?)
+        }
+        None => None,
+    };
+    let workspace_root = cargo_toml_path
+        .as_deref()
+        .and_then(Path::parent)
+        .filter(|_| manifest.as_ref().is_some_and(|m| m.get("workspace").is_some()));
+
+    // Grab the name from --title or the [package] table, falling back to a virtual workspace's directory name
+    let project_name = args
+        .title
+        .clone()
+        .or_else(|| manifest.as_ref().and_then(package_name))
+        .or_else(|| {
+            let root = fs::canonicalize(workspace_root?).ok()?;
+            Some(root.file_name()?.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "Unnamed Project".to_string());
+
+    // Label sections with their owning crate when walking a workspace
+    let members = match (workspace_root, &manifest) {
+        (Some(root), Some(manifest)) => workspace_members(root, manifest)?,
+        _ => Vec::new(),
+    };
+
+    // We'll accumulate one section per file, then render them at the end
+    let mut sections: Vec<Section> = Vec::new();
+
+    // Resolve the set of files touched on this branch (or since a revision) before walking
+    let changed_files = if args.branch_changes.is_some() || args.changed_since.is_some() {
+        let mut changed = HashSet::new();
+        for root in &args.dirs {
+            let dir = if root.is_file() { root.parent().unwrap_or(Path::new(".")) } else { root };
+            if let Some(base) = &args.branch_changes {
+                changed.extend(git_branch_changes(dir, base)?);
+            }
+            if let Some(revision) = &args.changed_since {
+                changed.extend(git_changed_since(dir, revision)?);
+            }
+        }
+        Some(changed)
     } else {
-        "Unnamed Project".to_string()
+        None
     };
 
-    // We'll accumulate our output in a String, then print at the end
-    let mut markdown_output = String::new();
+    // The crate root, standing in for all of the crate's Rust files, and its expanded source
+    let expanded = if args.expand_macros {
+        let manifest_dir = cargo_toml_path
+            .as_deref()
+            .and_then(Path::parent)
+            .ok_or_else(|| anyhow::anyhow!("--expand-macros needs a Cargo.toml in the first directory"))?;
+        let root = ["src/lib.rs", "src/main.rs"]
+            .iter()
+            .map(|file| manifest_dir.join(file))
+            .find(|path| path.is_file())
+            .ok_or_else(|| anyhow::anyhow!("--expand-macros couldn't find src/lib.rs or src/main.rs"))?;
+        Some((fs::canonicalize(&root)?, cargo_expand(manifest_dir)?))
+    } else {
+        None
+    };
 
-    // Build a walker that respects .gitignore files by default
-    let walker = WalkBuilder::new(&args.dir)
-        .git_ignore(true)  // enable .gitignore parsing
-        .build();
+    // Anonymized output depends on every file's names, so it can't come from the cache
+    let cache = if args.no_cache || args.list || args.anonymize { None } else { Cache::open() };
+    let mut warnings: Vec<Warning> = Vec::new();
+    let started = Instant::now();
+    let listed = args.files_from.as_deref().map(read_file_list).transpose()?;
+    // Files some enabled language matches, counted against --max-files across every directory
+    let mut found = 0;
+    let mut walked: Walked = match &listed {
+        // Listed files are taken as given, like files named on the command line
+        Some(listed) => listed.iter().map(|path| (path, vec![path.clone()])).collect(),
+        None => args
+            .dirs
+            .iter()
+            .map(|root| (root, walk_root(root, &args, changed_files.as_ref(), &mut found, &mut warnings)))
+            .collect(),
+    };
+    info!(
+        "Found {} file(s) in {:.2?}",
+        walked.iter().map(|(_, files)| files.len()).sum::<usize>(),
+        started.elapsed()
+    );
 
-    for result in walker {
-        match result {
-            Ok(entry) => {
-                if entry.file_type().map_or(false, |ft| ft.is_file()) {
-                    let path = entry.path();
-                    // Process Rust files
-                    if path.extension().and_then(|s| s.to_str()) == Some("rs") {
-                        match process_rust_file(path, args.remove_docs) {
-                            Ok(minified) => {
-                                markdown_output.push_str(&format!(
-                                    "## {}\n```rust\n{}\n```\n",
-                                    path.display(),
-                                    minified
-                                ));
-                            }
-                            Err(e) => {
-                                eprintln!("Error processing {}: {}", path.display(), e);
-                            }
-                        }
+    if args.count_only {
+        let mut out = open_output(&args)?;
+        let mut warnings = Vec::new();
+        write!(out, "{}", line_count_table(&walked, &args, &mut warnings))?;
+        out.flush()?;
+        print_warnings(&warnings);
+        return Ok(());
+    }
+
+    if args.interactive {
+        match pick_files(walked, &args)? {
+            Some(picked) if picked.iter().any(|(_, files)| !files.is_empty()) => walked = picked,
+            _ => {
+                eprintln!("No files picked, so no prompt was written");
+                return Ok(());
+            }
+        }
+    }
+
+    // Names are gathered from every Rust file up front so each keeps one placeholder throughout
+    let anonymizer = args.anonymize.then(|| {
+        let mut anonymizer = Anonymizer::default();
+        for path in walked.iter().flat_map(|(_, files)| files) {
+            if handlers_for(path, &args).iter().any(|handler| matches!(handler, Handler::Rust))
+                && let Ok(code) = fs::read_to_string(path)
+            {
+                anonymizer.collect(&code);
+            }
+        }
+        anonymizer
+    });
+
+    let prelude = match (&args.prelude, &args.prelude_file) {
+        (Some(text), _) => Some(text.clone()),
+        (None, Some(path)) => Some(
+            fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("couldn't read prelude file {}: {}", path.display(), e))?,
+        ),
+        (None, None) => None,
+    };
+
+    let dependencies = manifest.as_ref().filter(|_| args.with_deps).map(dependency_summary);
+    let header = match args.format {
+        Format::Markdown => {
+            let mut header = match prelude {
+                Some(prelude) => format!("{}\n\n# {}\n", prelude.trim_end(), project_name),
+                None => format!("# {}\n", project_name),
+            };
+            header.push_str(dependencies.as_deref().unwrap_or_default());
+            header
+        }
+        Format::Xml => format!(
+            "<project name=\"{}\">\n{}{}",
+            xml_attribute(&project_name),
+            args.format.block("instructions", prelude.as_deref().unwrap_or_default()),
+            args.format.block("dependencies", dependencies.as_deref().unwrap_or_default())
+        ),
+        Format::Jsonl => String::new(),
+    };
+
+    // Unless something needs every section at once (sorting, a token budget, the clipboard),
+    // each file is written out as soon as it's processed rather than held until the end
+    let mut stream = (!args.list
+        && args.group_by.is_none()
+        && args.max_tokens.is_none()
+        && !args.clipboard
+        && !args.toc
+        && args.split_by_dir.is_none()
+        && args.template.is_none())
+        .then(|| open_output(&args))
+        .transpose()?;
+    let mut stats = Stats::default();
+    let mut markdown_chars = 0;
+    if let Some(out) = &mut stream {
+        write!(out, "{}", header)?;
+        out.flush()?;
+    }
+
+    let progress = progress_bar(&args);
+    let mut flat_names = args.flatten.then(FlatNames::default);
+    let mut redactions = 0;
+    // Content to the display path of the first file with it, for --dedupe. Keyed by the
+    // content itself rather than a hash of it, so files that merely collide are never merged.
+    let mut first_paths: Option<HashMap<String, String>> = args.dedupe.then(HashMap::new);
+    // `(display path, comment characters per code character)` for --comment-report
+    let mut densities: Vec<(String, f64)> = Vec::new();
+    // Files matched by some language
+    let mut matched = 0;
+    // `(display path, SHA-256 of the file as read)` for --manifest
+    let mut file_hashes: Vec<(String, String)> = Vec::new();
+    let started = Instant::now();
+    for (root, files) in walked {
+        for path in files {
+            let handlers = handlers_for(&path, &args);
+            if handlers.is_empty() {
+                info!("Skipping {}: no enabled language matches it", path.display());
+                continue;
+            }
+            let expanded_root = expanded
+                .as_ref()
+                .filter(|(root, _)| fs::canonicalize(&path).is_ok_and(|path| path == *root));
+            if expanded.is_some() && expanded_root.is_none() && path.extension().is_some_and(|ext| ext == "rs") {
+                info!("Skipping {}: part of the expanded crate", path.display());
+                continue;
+            }
+            matched += 1;
+            let names: Vec<&str> = handlers.iter().map(|handler| handler.name()).collect();
+            info!("Processing {} as {}", path.display(), names.join(", "));
+            progress.set_message(path.display().to_string());
+            // Listed under the name a normal run would give its section
+            if args.list {
+                let shown = match &mut flat_names {
+                    Some(flat_names) => flat_names.name(&path),
+                    None => display_path(&path, root, args.path_prefix.as_deref()),
+                };
+                for name in &names {
+                    println!("{}\t{}", shown, name);
+                }
+                progress.inc(1);
+                continue;
+            }
+            let first_section = sections.len();
+            visit_path(&path, &args, cache.as_ref(), anonymizer.as_ref(), &mut sections, &mut warnings)?;
+            progress.inc(1);
+
+            if let Some((_, code)) = expanded_root
+                && let Some(section) = sections.get_mut(first_section)
+            {
+                section.content = process_rust_file(&path, code.clone(), &args, anonymizer.as_ref(), &mut warnings);
+            }
+
+            for section in &mut sections[first_section..] {
+                section.display_path = match &mut flat_names {
+                    Some(flat_names) => flat_names.name(&section.path),
+                    None => display_path(&section.path, root, args.path_prefix.as_deref()),
+                };
+                if expanded_root.is_some() {
+                    section.display_path.push_str(" (macros expanded)");
+                }
+                if let Some(command) = &args.filter_cmd {
+                    match run_filter(command, &section.path, &section.content) {
+                        Ok(filtered) => section.content = filtered,
+                        Err(e) => warnings.push(Warning::new(&section.path, WarningKind::Filter, e)),
                     }
-                    
-                    // Process JavaScript files (if the flag is set)
-                    if (args.javascript || args.all) && path.extension().and_then(|s| s.to_str()) == Some("js") {
-                        match process_javascript_file(path, args.remove_docs) {
-                            Ok(minified) => {
-                                markdown_output.push_str(&format!(
-                                    "## {}\n```javascript\n{}\n```\n",
-                                    path.display(),
-                                    minified
-                                ));
-                            }
-                            Err(e) => {
-                                eprintln!("Error processing {}: {}", path.display(), e);
-                            }
+                }
+                redactions += finish_section(section, &args, &members);
+
+                if let Some(first_paths) = &mut first_paths {
+                    match first_paths.get(&section.content) {
+                        Some(first) => {
+                            debug!("{} has the same content as {}", section.display_path, first);
+                            section.duplicate_of = Some(first.clone());
+                            section.content.clear();
+                            section.symbols = None;
+                            section.content_stats = None;
+                        }
+                        None => {
+                            first_paths.insert(section.content.clone(), section.display_path.clone());
                         }
                     }
-                    
-                    // Python
-                    if (args.python || args.all) && (path.extension().and_then(|s| s.to_str()) == Some("py") || path.extension().and_then(|s| s.to_str()) == Some("pyw")) {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["__pycache__".to_string(), "venv".to_string(), ".env".to_string(), "dist".to_string()];
-                        let line_comment = "#".to_string();
-                        let block_comment_start = "'''".to_string();
-                        let block_comment_end = "'''".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(&file_contents, &line_comment, &block_comment_start, &block_comment_end)
-                        } else {
-                            file_contents
-                        };
-                        
-                        let minified = remove_whitespace(&stripped);
-                        
-                        markdown_output.push_str(&format!(
-                            "## {}\n```python\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Java
-                    if (args.java || args.all) && path.extension().and_then(|s| s.to_str()) == Some("java") {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["target".to_string(), "build".to_string(), "out".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(&file_contents, &line_comment, &block_comment_start, &block_comment_end)
-                        } else {
-                            file_contents
-                        };
-                        
-                        let minified = remove_whitespace(&stripped);
-                        
-                        markdown_output.push_str(&format!(
-                            "## {}\n```java\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // C / C++
-                    if (args.cpp || args.all) && 
-                        (
-                        path.extension().and_then(|s| s.to_str()) == Some("cpp") || 
-                        path.extension().and_then(|s| s.to_str()) == Some("hpp") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("cc") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("hh") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("cxx") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("hxx") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("c") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("h") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("m") ||
-                        path.extension().and_then(|s| s.to_str()) == Some("mm")
-                        ) {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["build".to_string(), "obj".to_string(), "bin".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(&file_contents, &line_comment, &block_comment_start, &block_comment_end)
-                        } else {
-                            file_contents
-                        };
-                        
-                        let minified = remove_whitespace(&stripped);
-                        
-                        markdown_output.push_str(&format!(
-                            "## {}\n```c/c++/obj-c\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // C#
-                    if (args.csharp || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("cs"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec![
-                            "bin".to_string(),
-                            "obj".to_string(),
-                            "Debug".to_string(),
-                            "Release".to_string(),
-                        ];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```csharp\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // PHP
-                    if (args.php || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("php"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["vendor".to_string(), "cache".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```php\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Ruby
-                    if (args.ruby || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("rb"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["vendor".to_string(), "tmp".to_string(), "log".to_string()];
-                        let line_comment = "#".to_string();
-                        let block_comment_start = "=begin".to_string();
-                        let block_comment_end = "=end".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```ruby\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Swift
-                    if (args.swift || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("swift"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec![".build".to_string(), "Pods".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```swift\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // TypeScript
-                    if (args.typescript || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("ts")
-                            || path.extension().and_then(|s| s.to_str()) == Some("tsx")
-                        )
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec![
-                            "node_modules".to_string(),
-                            "dist".to_string(),
-                            "build".to_string(),
-                        ];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```typescript\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Kotlin
-                    if (args.kotlin || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("kt")
-                            || path.extension().and_then(|s| s.to_str()) == Some("kts")
-                        )
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["build".to_string(), "out".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```kotlin\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Go
-                    if (args.go || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("go"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["vendor".to_string(), "bin".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```go\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // R
-                    if (args.r || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("r")
-                            || path.extension().and_then(|s| s.to_str()) == Some("R")
-                        )
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["renv".to_string()];
-                        let line_comment = "#".to_string();
-                        // R doesn't truly have traditional block comments
-                        let block_comment_start = "".to_string();
-                        let block_comment_end = "".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```r\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // MATLAB
-                    if (args.matlab || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("m"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["bin".to_string()];
-                        let line_comment = "%".to_string();
-                        let block_comment_start = "%{".to_string();
-                        let block_comment_end = "%}".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```matlab\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // VB.NET
-                    if (args.vbnet || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("vb"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["bin".to_string(), "obj".to_string()];
-                        let line_comment = "'".to_string();
-                        // VB.NET uses line comments primarily
-                        let block_comment_start = "".to_string();
-                        let block_comment_end = "".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```vbnet\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Scala
-                    if (args.scala || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("scala"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["target".to_string(), "project/target".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```scala\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Perl
-                    if (args.perl || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("pl")
-                            || path.extension().and_then(|s| s.to_str()) == Some("pm")
-                        )
+                }
+            }
+            if args.manifest.is_some()
+                && let Some(section) = sections.get(first_section)
+            {
+                match fs::read(&path) {
+                    Ok(bytes) => file_hashes.push((section.display_path.clone(), sha256_hex(&bytes))),
+                    Err(e) => warnings.push(Warning::new(&path, WarningKind::Unreadable, e)),
+                }
+            }
+            if args.comment_report
+                && let Some(section) = sections.get(first_section)
+                && let Some((comment, code)) = comment_density(&path, &args)
+                && code > 0
+            {
+                densities.push((section.display_path.clone(), comment as f64 / code as f64));
+            }
+            if let Some(out) = &mut stream {
+                for section in sections.drain(first_section..) {
+                    let rendered = section.render(args.format);
+                    markdown_chars += rendered.chars().count();
+                    stats.add(&section);
+                    write!(out, "{}", rendered)?;
+                }
+                out.flush()?;
+            }
+        }
+    }
+
+    progress.finish_and_clear();
+    info!("Processed {} file(s) in {:.2?}", matched, started.elapsed());
+    print_warnings(&warnings);
+    if matched == 0 {
+        eprintln!(
+            "Warning: no matching files found. Only Rust is included by default; add language flags such as -p (Python) or -a (all languages)"
+        );
+    }
+    if found > args.max_files {
+        eprintln!(
+            "Stopped walking at --max-files {}; there are more matching files that were left out",
+            args.max_files
+        );
+    }
+
+    if args.comment_report {
+        densities.sort_by(|a, b| a.1.total_cmp(&b.1));
+        eprintln!("Comment density (comment / code characters), least documented first:");
+        for (path, density) in &densities {
+            eprintln!("  {:.2}  {}", density, path);
+        }
+    }
+
+    // Nothing was processed, the file list has already been printed
+    if args.list {
+        return Ok(());
+    }
+
+    if let Some(GroupBy::Language) = args.group_by {
+        sections.sort_by(|a, b| (a.fence, &a.display_path).cmp(&(b.fence, &b.display_path)));
+    }
+
+    if args.redact || redactions > 0 {
+        eprintln!("Redacted {} likely secret(s)", redactions);
+    }
+
+    // Files left out for the token budget are only known further down
+    let budgeted = args.max_tokens.is_some() && args.chunk.is_none();
+    if let Some(path) = &args.manifest
+        && !budgeted
+    {
+        write_manifest(path, &file_hashes)?;
+    }
+
+    if let Some(mut out) = stream {
+        if !args.no_stats {
+            let tokens = estimate_tokens(&header) + markdown_chars.div_ceil(4);
+            write!(out, "{}", args.format.block("stats", &stats.footer(tokens)))?;
+        }
+        write!(out, "{}", args.format.closing())?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    // Split into several files that each fit the budget
+    if let (Some(budget), Some(prefix)) = (args.max_tokens, &args.chunk) {
+        let chunks = chunk_sections(&sections, &header, budget);
+        for (i, chunk) in chunks.iter().enumerate() {
+            let chunk_path = format!("{}.part{}.md", prefix, i + 1);
+            fs::write(&chunk_path, chunk)?;
+            eprintln!("Wrote {} (~{} tokens)", chunk_path, estimate_tokens(chunk));
+        }
+        return Ok(());
+    }
+
+    if let Some(dir) = &args.split_by_dir {
+        fs::create_dir_all(dir).map_err(|e| anyhow::anyhow!("couldn't create {}: {}", dir.display(), e))?;
+        for (group, sections) in group_by_directory(&sections) {
+            let mut prompt = header.clone();
+            let mut stats = Stats::default();
+            for section in &sections {
+                prompt.push_str(&section.to_markdown());
+                stats.add(section);
+            }
+            let tokens = estimate_tokens(&prompt);
+            if !args.no_stats {
+                prompt.push_str(&stats.footer(tokens));
+            }
+            let path = dir.join(format!("{}-prompt.md", group));
+            fs::write(&path, &prompt).map_err(|e| anyhow::anyhow!("couldn't write {}: {}", path.display(), e))?;
+            eprintln!("Wrote {} ({} file(s), ~{} tokens)", path.display(), sections.len(), tokens);
+        }
+        return Ok(());
+    }
+
+    // Drop whole files that don't fit the budget
+    let omitted = match args.max_tokens {
+        Some(budget) => {
+            // The stats footer comes after the files, so keep room for it
+            let footer = if args.no_stats {
+                0
+            } else {
+                let mut all = Stats::default();
+                for section in &sections {
+                    all.add(section);
+                }
+                estimate_tokens(&args.format.block("stats", &all.footer(budget)))
+            };
+            apply_token_budget(&mut sections, estimate_tokens(&header) + footer, budget, args.format)
+        }
+        None => Vec::new(),
+    };
+    if let Some((model, window)) = args.model
+        && args.max_tokens == Some(window)
+        && !omitted.is_empty()
+    {
+        eprintln!(
+            "Warning: the prompt exceeds {}'s context window of {} tokens; {} file(s) were left out",
+            model,
+            window,
+            omitted.len()
+        );
+    }
+    if let Some(path) = &args.manifest {
+        file_hashes.retain(|(display_path, _)| !omitted.contains(display_path));
+        write_manifest(path, &file_hashes)?;
+    }
+
+    if let Some(path) = &args.template {
+        let mut out = open_output(&args)?;
+        write!(out, "{}", template::render(path, &project_name, &sections, &omitted)?)?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    let mut markdown_output = if args.toc {
+        table_of_contents(&header, &sections, args.group_by.is_some())
+    } else {
+        String::new()
+    };
+    for (i, section) in sections.iter().enumerate() {
+        if args.group_by.is_some()
+            && args.format == Format::Markdown
+            && (i == 0 || sections[i - 1].fence != section.fence)
+        {
+            markdown_output.push_str(&format!("# {}\n", language_heading(section.fence)));
+        }
+        markdown_output.push_str(&section.render(args.format));
+    }
+    if let Some(budget) = args.max_tokens {
+        markdown_output.push_str(&args.format.block("omitted", &omitted_note(&omitted, budget)));
+    }
+    if !args.no_stats {
+        for section in &sections {
+            stats.add(section);
+        }
+        let tokens = estimate_tokens(&header) + estimate_tokens(&markdown_output);
+        markdown_output.push_str(&args.format.block("stats", &stats.footer(tokens)));
+    }
+    markdown_output.push_str(args.format.closing());
+
+    if args.clipboard {
+        let prompt = format!("{}{}", header, markdown_output);
+        match copy_to_clipboard(&prompt) {
+            Ok(()) => {
+                eprintln!("Copied prompt to clipboard (~{} tokens)", estimate_tokens(&prompt));
+                return Ok(());
+            }
+            Err(e) => eprintln!("Warning: couldn't copy to the clipboard ({}), printing instead", e),
+        }
+    }
+
+    // Print the final markdown document to stdout
+    let mut out = open_output(&args)?;
+    write!(out, "{}", header)?;
+    write!(out, "{}", markdown_output)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Writes the `--manifest` JSON: the tool version, the arguments it was run with, and
+/// `{path, sha256}` for each included file, so a later run can check nothing changed.
+fn write_manifest(path: &Path, file_hashes: &[(String, String)]) -> anyhow::Result<()> {
+    let files: Vec<serde_json::Value> = file_hashes
+        .iter()
+        .map(|(path, hash)| serde_json::json!({ "path": path, "sha256": hash }))
+        .collect();
+    let manifest = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        // Everything after `prompt`, as typed
+        "arguments": command_line()
+            .iter()
+            .skip(2)
+            .map(|argument| argument.to_string_lossy())
+            .collect::<Vec<_>>(),
+        "files": files,
+    });
+    fs::write(path, format!("{:#}\n", manifest))
+        .map_err(|e| anyhow::anyhow!("couldn't write manifest {}: {}", path.display(), e))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Opens where the prompt is written: stdout, or the `--output` file, compressed when
+/// `--compress` or the file's extension asks for it.
+fn open_output(args: &Cli) -> anyhow::Result<Box<dyn Write>> {
+    let Some(path) = &args.output else {
+        return Ok(Box::new(std::io::BufWriter::new(std::io::stdout().lock())));
+    };
+    let compression = args.compress.or(match path.extension().and_then(|s| s.to_str()) {
+        Some("gz") => Some(Compression::Gzip),
+        Some("zst") => Some(Compression::Zstd),
+        _ => None,
+    });
+    match compression {
+        Some(compression) => compressed(path, compression),
+        None => Ok(Box::new(create_output_file(path)?)),
+    }
+}
+
+fn create_output_file(path: &Path) -> anyhow::Result<std::io::BufWriter<fs::File>> {
+    let file = fs::File::create(path).map_err(|e| anyhow::anyhow!("couldn't create {}: {}", path.display(), e))?;
+    Ok(std::io::BufWriter::new(file))
+}
+
+/// Creates `path` behind an encoder that finishes the compressed stream when dropped.
+#[cfg(feature = "compression")]
+fn compressed(path: &Path, compression: Compression) -> anyhow::Result<Box<dyn Write>> {
+    let file = create_output_file(path)?;
+    Ok(match compression {
+        Compression::Gzip => Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default())),
+        Compression::Zstd => Box::new(zstd::Encoder::new(file, 0)?.auto_finish()),
+    })
+}
+
+#[cfg(not(feature = "compression"))]
+fn compressed(_path: &Path, _compression: Compression) -> anyhow::Result<Box<dyn Write>> {
+    anyhow::bail!("compressed output needs cargo-prompt built with the `compression` feature")
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_text: &str) -> Result<(), String> {
+    Err("built without the `clipboard` feature".to_string())
+}
+
+/// Each directory walked (or file listed) with the files found there.
+type Walked<'a> = Vec<(&'a PathBuf, Vec<PathBuf>)>;
+
+/// Offers the files some enabled language matches in the `--interactive` picker and keeps
+/// the ones left checked. `None` when the user cancels.
+#[cfg(feature = "interactive")]
+fn pick_files<'a>(
+    walked: Walked<'a>,
+    args: &Cli,
+) -> anyhow::Result<Option<Walked<'a>>> {
+    if !std::io::stdout().is_terminal() {
+        anyhow::bail!("--interactive needs stdout to be a terminal; write the prompt with -o FILE instead of redirecting it");
+    }
+    let mut walked: Walked = walked
+        .into_iter()
+        .map(|(root, mut files)| {
+            files.retain(|path| !handlers_for(path, args).is_empty());
+            (root, files)
+        })
+        .collect();
+    let candidates: Vec<picker::Candidate> = walked
+        .iter()
+        .flat_map(|(root, files)| {
+            files.iter().map(|path| picker::Candidate {
+                display_path: display_path(path, root, args.path_prefix.as_deref()),
+                tokens: fs::metadata(path).map_or(0, |m| m.len() as usize).div_ceil(4),
+            })
+        })
+        .collect();
+
+    let Some(checked) = picker::pick(&candidates)? else {
+        return Ok(None);
+    };
+    // `retain` visits files in order, matching the order they were offered in
+    let mut checked = checked.into_iter();
+    for (_, files) in &mut walked {
+        files.retain(|_| checked.next().unwrap_or(false));
+    }
+    Ok(Some(walked))
+}
+
+#[cfg(not(feature = "interactive"))]
+fn pick_files<'a>(
+    _walked: Walked<'a>,
+    _args: &Cli,
+) -> anyhow::Result<Option<Walked<'a>>> {
+    anyhow::bail!("--interactive needs cargo-prompt built with the `interactive` feature")
+}
+
+/// Summarizes the edition, rust-version, and dependency tables of a parsed Cargo.toml.
+fn dependency_summary(manifest: &toml::Value) -> String {
+    let mut summary = String::from("## Dependencies\n");
+
+    let package = manifest.get("package");
+    for key in ["edition", "rust-version"] {
+        match package.and_then(|pkg| pkg.get(key)) {
+            Some(toml::Value::String(value)) => summary.push_str(&format!("- {}: {}\n", key, value)),
+            Some(toml::Value::Table(table)) if is_workspace_inherited(table) => {
+                summary.push_str(&format!("- {}: workspace-inherited\n", key))
+            }
+            _ => {}
+        }
+    }
+
+    for table_name in ["dependencies", "dev-dependencies"] {
+        let Some(table) = manifest.get(table_name).and_then(|deps| deps.as_table()) else {
+            continue;
+        };
+        summary.push_str(&format!("### [{}]\n", table_name));
+        for (name, spec) in table {
+            summary.push_str(&format!("- {}\n", describe_dependency(name, spec)));
+        }
+    }
+
+    summary
+}
+
+/// Formats one dependency entry, e.g. `clap = 4.5 (features: derive)`.
+fn describe_dependency(name: &str, spec: &toml::Value) -> String {
+    let table = match spec {
+        toml::Value::Table(table) => table,
+        other => return format!("{} = {}", name, other.as_str().unwrap_or_default()),
+    };
+
+    if is_workspace_inherited(table) {
+        return format!("{} (workspace-inherited)", name);
+    }
+
+    let mut description = match table.get("version").and_then(|v| v.as_str()) {
+        Some(version) => format!("{} = {}", name, version),
+        None => name.to_string(),
+    };
+    let mut details = Vec::new();
+    for key in ["path", "git", "branch", "tag", "rev"] {
+        if let Some(value) = table.get(key).and_then(|v| v.as_str()) {
+            details.push(format!("{}: {}", key, value));
+        }
+    }
+    if let Some(features) = table.get("features").and_then(|v| v.as_array()) {
+        let features: Vec<&str> = features.iter().filter_map(|f| f.as_str()).collect();
+        details.push(format!("features: {}", features.join(", ")));
+    }
+    if table.get("optional").and_then(|v| v.as_bool()) == Some(true) {
+        details.push("optional".to_string());
+    }
+    if !details.is_empty() {
+        description.push_str(&format!(" ({})", details.join("; ")));
+    }
+    description
+}
+
+/// True for `{ workspace = true }` style entries inherited from the workspace root.
+fn is_workspace_inherited(table: &toml::map::Map<String, toml::Value>) -> bool {
+    table.get("workspace").and_then(|v| v.as_bool()) == Some(true)
+}
+
+/// Discovers the member crates of a Cargo workspace as `(canonical directory, package name)`
+/// pairs, deepest directories first so nested members take precedence.
+fn workspace_members(root: &Path, manifest: &toml::Value) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let root = fs::canonicalize(root)?;
+    let members = workspace_globs(manifest, "members")?;
+    let excluded = workspace_globs(manifest, "exclude")?;
+
+    let mut crates = Vec::new();
+    // A root manifest may be both a package and a workspace
+    if let Some(name) = package_name(manifest) {
+        crates.push((root.clone(), name));
+    }
+
+    for entry in WalkBuilder::new(&root).build().flatten() {
+        if entry.file_name() != "Cargo.toml" {
+            continue;
+        }
+        let Some(dir) = entry.path().parent() else {
+            continue;
+        };
+        let Ok(relative) = dir.strip_prefix(&root) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() || !members.is_match(relative) || excluded.is_match(relative) {
+            continue;
+        }
+
+        let contents = fs::read_to_string(entry.path())?;
+        let member_manifest: toml::Value = toml::from_str(&contents)?;
+        if let Some(name) = package_name(&member_manifest) {
+            crates.push((dir.to_path_buf(), name));
+        }
+    }
+
+    crates.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+    Ok(crates)
+}
+
+/// Compiles the glob list stored under `[workspace].<key>`.
+fn workspace_globs(manifest: &toml::Value, key: &str) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    let patterns = manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get(key))
+        .and_then(|patterns| patterns.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|pattern| pattern.as_str());
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern.trim_end_matches('/'))
+                .literal_separator(true)
+                .build()?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+fn package_name(manifest: &toml::Value) -> Option<String> {
+    manifest
+        .get("package")
+        .and_then(|pkg| pkg.get("name"))
+        .and_then(|name| name.as_str())
+        .map(str::to_owned)
+}
+
+/// Finds the workspace member containing `path`, if any.
+fn owning_crate(members: &[(PathBuf, String)], path: &Path) -> Option<String> {
+    if members.is_empty() {
+        return None;
+    }
+    let path = fs::canonicalize(path).ok()?;
+    members
+        .iter()
+        .find(|(dir, _)| path.starts_with(dir))
+        .map(|(_, name)| name.clone())
+}
+
+/// Lists every file under `root` that passes the ignore, generated-file, and changed-file
+/// filters, or `root` itself when it is a file. `found` counts the files some enabled
+/// language matches, across calls, and the walk ends once it passes `--max-files`.
+fn walk_root(
+    root: &Path,
+    args: &Cli,
+    changed_files: Option<&HashSet<PathBuf>>,
+    found: &mut usize,
+    warnings: &mut Vec<Warning>,
+) -> Vec<PathBuf> {
+    // Individual files are processed directly, regardless of ignore rules
+    if root.is_file() {
+        return vec![root.to_path_buf()];
+    }
+    // An earlier directory already reached --max-files
+    if *found > args.max_files {
+        return Vec::new();
+    }
+
+    // Build a walker that respects .gitignore files by default
+    let walker = WalkBuilder::new(root)
+        .git_ignore(!args.no_gitignore)  // enable .gitignore parsing
+        .ignore(!args.no_gitignore)
+        .git_exclude(!args.no_gitignore)
+        // Apply ignore files from parent directories and the user's global gitignore, as git would
+        .parents(!args.no_gitignore)
+        .git_global(!args.no_gitignore)
+        .hidden(!args.hidden)
+        .max_depth(args.depth)
+        // The walker detects symlink loops and reports them as errors
+        .follow_links(args.follow_symlinks)
+        .build();
+
+    // Generated and vendored code is skipped unless asked for
+    let attributes = (!args.include_generated).then(|| GitAttributes::load(root));
+
+    let mut files = Vec::new();
+    for result in walker {
+        match result {
+            Ok(entry) => {
+                if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                    let path = entry.path();
+
+                    if let Some(attributes) = &attributes
+                        && attributes.is_generated(path)
                     {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["blib".to_string(), "_build".to_string()];
-                        let line_comment = "#".to_string();
-                        let block_comment_start = "=pod".to_string();
-                        let block_comment_end = "=cut".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```perl\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
+                        info!("Skipping {}: marked generated or vendored in .gitattributes", path.display());
+                        continue;
                     }
-                    
-                    // Dart
-                    if (args.dart || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("dart"))
+
+                    // Skip files that --branch-changes / --changed-since rule out
+                    if let Some(changed) = changed_files
+                        && !fs::canonicalize(path).is_ok_and(|p| changed.contains(&p))
                     {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["build".to_string(), ".dart_tool".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```dart\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
+                        debug!("Skipping {}: unchanged", path.display());
+                        continue;
                     }
-                    
-                    // Groovy
-                    if (args.groovy || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("groovy")
-                            || path.extension().and_then(|s| s.to_str()) == Some("gvy")
-                            || path.extension().and_then(|s| s.to_str()) == Some("gy")
-                            || path.extension().and_then(|s| s.to_str()) == Some("gsh")
-                        )
+                    if let Some(within) = args.modified_within
+                        && !modified_within(path, within)
                     {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["target".to_string(), "build".to_string()];
-                        let line_comment = "//".to_string();
-                        let block_comment_start = "/*".to_string();
-                        let block_comment_end = "*/".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```groovy\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
+                        debug!("Skipping {}: not modified in the last {}", path.display(), humantime::format_duration(within));
+                        continue;
                     }
-                    
-                    // Julia
-                    if (args.julia || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("jl"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["docs/build".to_string()];
-                        let line_comment = "#".to_string();
-                        let block_comment_start = "#=".to_string();
-                        let block_comment_end = "=#".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```julia\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
+                    if args.no_tests || args.only_tests {
+                        // Directories above the one being walked don't make a file a test
+                        let is_test = test_files::is_test(path.strip_prefix(root).unwrap_or(path));
+                        if is_test == args.no_tests {
+                            debug!("Skipping {}: {}", path.display(), if is_test { "a test" } else { "not a test" });
+                            continue;
+                        }
                     }
-                    
-                    // Haskell
-                    if (args.haskell || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("hs")
-                            || path.extension().and_then(|s| s.to_str()) == Some("lhs")
-                        )
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["dist".to_string(), ".stack-work".to_string()];
-                        let line_comment = "--".to_string();
-                        let block_comment_start = "{-".to_string();
-                        let block_comment_end = "-}".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
-                        } else {
-                            file_contents
-                        };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```haskell\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
+
+                    // The walk itself stops at the limit, so a run in `/` ends quickly. `found`
+                    // is left one over the limit to say so.
+                    if !handlers_for(path, args).is_empty() {
+                        *found += 1;
+                        if *found > args.max_files {
+                            info!("Stopping at {}: over --max-files", path.display());
+                            break;
+                        }
                     }
-                    
-                    // Shell/Bash
-                    if (args.shell || args.all)
-                        && (
-                            path.extension().and_then(|s| s.to_str()) == Some("sh")
-                            || path.extension().and_then(|s| s.to_str()) == Some("bash")
-                        )
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["tmp".to_string()];
-                        let line_comment = "#".to_string();
-                        // Shell typically uses only line comments
-                        let block_comment_start = "".to_string();
-                        let block_comment_end = "".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
+
+                    files.push(entry.into_path());
+                }
+            }
+            Err(e) => {
+                // An unreadable directory entry only loses that entry
+                warnings.push(Warning::new(root, WarningKind::Unreadable, e));
+            }
+        }
+    }
+    files
+}
+
+/// A spinner on stderr counting visited files, or a hidden bar unless `--progress` was
+/// given and stderr is a terminal. The total isn't known until the walk finishes.
+fn progress_bar(args: &Cli) -> ProgressBar {
+    if !args.progress || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let progress = ProgressBar::new_spinner().with_style(
+        ProgressStyle::with_template("{spinner} {pos} files {wide_msg}").expect("valid progress template"),
+    );
+    progress.enable_steady_tick(std::time::Duration::from_millis(100));
+    progress
+}
+
+/// Formats `path` relative to the walk `root` with `/` separators, under `prefix` if given.
+/// A file passed directly is shown as given.
+fn display_path(path: &Path, root: &Path, prefix: Option<&str>) -> String {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        _ => path,
+    };
+    let mut parts: Vec<String> = relative
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .map(|component| match component {
+            // Joined with "/" below, so an absolute path keeps a single leading slash
+            Component::RootDir => String::new(),
+            _ => component.as_os_str().to_string_lossy().replace('\\', "/"),
+        })
+        .collect();
+    if let Some(prefix) = prefix.map(|p| p.trim_end_matches(['/', '\\'])).filter(|p| !p.is_empty()) {
+        parts.insert(0, prefix.replace('\\', "/"));
+    }
+    parts.join("/")
+}
+
+/// File names handed out by `--flatten`, numbering repeats in walk order: `mod.rs`,
+/// `mod.rs (2)`, ... Sections from the same file share a name.
+#[derive(Default)]
+struct FlatNames {
+    names: HashMap<PathBuf, String>,
+    counts: HashMap<String, usize>,
+}
+
+impl FlatNames {
+    fn name(&mut self, path: &Path) -> String {
+        let counts = &mut self.counts;
+        self.names
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let file_name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+                let count = counts.entry(file_name.clone()).or_insert(0);
+                *count += 1;
+                if *count == 1 { file_name } else { format!("{} ({})", file_name, count) }
+            })
+            .clone()
+    }
+}
+
+/// Counts the non-whitespace characters of `path` inside and outside comments, using the
+/// comment syntax of its first handler. `None` for notebooks, raw text, and unreadable files.
+fn comment_density(path: &Path, args: &Cli) -> Option<(usize, usize)> {
+    // Read as the file's section was, whose warnings have been reported already
+    let source = read_source(path, args.encoding, &mut Vec::new())?;
+    let (text, mask) = comments_in(&source, handlers_for(path, args).first()?)?;
+    let code_chars = text.chars().zip(mask).filter(|&(c, comment)| !comment && !c.is_whitespace()).count();
+    let visible = source.chars().filter(|c| !c.is_whitespace()).count();
+    Some((visible - code_chars, code_chars))
+}
+
+/// Marks the comments in `source` as `handler` sees them. Returns the text the mask applies
+/// to, which differs from `source` only where column comments were blanked, or `None` for
+/// handlers that don't know about comments.
+fn comments_in(source: &str, handler: &Handler) -> Option<(String, Vec<bool>)> {
+    let text = match handler {
+        Handler::Generic(LanguageSpec { column_comments: Some((column, markers)), .. }) => {
+            remove_column_comments(source, *column, markers)
+        }
+        _ => source.to_string(),
+    };
+    let mask = handler_char_kinds(&text, handler)?
+        .into_iter()
+        .map(|kind| kind == CharKind::Comment)
+        .collect();
+    Some((text, mask))
+}
+
+/// Classifies each character of `source` with `handler`'s comment and string syntax, or
+/// `None` for handlers that don't know about comments.
+fn handler_char_kinds(source: &str, handler: &Handler) -> Option<Vec<CharKind>> {
+    Some(match handler {
+        Handler::Rust => char_kinds(source, &["//"], &[("/*", "*/")], true, RawStrings::Rust),
+        Handler::JavaScript => char_kinds(source, &["//"], &[("/*", "*/")], false, RawStrings::None),
+        Handler::Generic(spec) => char_kinds(
+            source,
+            spec.line_comments,
+            spec.block_comments,
+            spec.nested_block_comments,
+            RawStrings::of(spec.name),
+        ),
+        Handler::Notebook | Handler::Raw => return None,
+    })
+}
+
+/// The handler whose output is labeled `fence`.
+fn fence_handler(fence: &str) -> Option<Handler> {
+    match fence {
+        "rust" => Some(Handler::Rust),
+        "javascript" => Some(Handler::JavaScript),
+        fence => LANGUAGES
+            .iter()
+            .chain(CONFIG_FORMATS)
+            .find(|spec| spec.fence == fence)
+            .map(Handler::Generic),
+    }
+}
+
+/// Per-language totals for `--count-only`.
+#[derive(Default)]
+struct LineCounts {
+    files: usize,
+    lines: usize,
+    blank: usize,
+    comment: usize,
+}
+
+impl LineCounts {
+    fn add(&mut self, other: &LineCounts) {
+        self.files += other.files;
+        self.lines += other.lines;
+        self.blank += other.blank;
+        self.comment += other.comment;
+    }
+
+    fn code(&self) -> usize {
+        self.lines - self.blank - self.comment
+    }
+}
+
+/// Classifies each line of `path`, read with `encoding`, as blank, comment (nothing but
+/// comments), or code.
+fn count_lines(path: &Path, handler: &Handler, encoding: Encoding, warnings: &mut Vec<Warning>) -> Option<LineCounts> {
+    let source = read_source(path, encoding, warnings)?;
+    let mut counts = LineCounts { files: 1, ..LineCounts::default() };
+    let comments = comments_in(&source, handler);
+    let mut stripped = comments.as_ref().map(|(text, mask)| (text.split_inclusive('\n'), mask.iter()));
+    for line in source.split_inclusive('\n') {
+        counts.lines += 1;
+        // Every line's share of the mask is used up, blank or not, to keep the two in step. A
+        // column comment on the last line comes back empty.
+        let code = match &mut stripped {
+            Some((lines, mask)) => lines
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .zip(mask)
+                .filter(|&(c, &comment)| !comment && !c.is_whitespace())
+                .count()
+                > 0,
+            None => true,
+        };
+        if line.trim().is_empty() {
+            counts.blank += 1;
+        } else if !code {
+            counts.comment += 1;
+        }
+    }
+    Some(counts)
+}
+
+/// The `--count-only` report: a cloc-style table of every walked file's language, with the
+/// most code first. Files that can't be read, or decoded with `--encoding`, are left out
+/// and listed in `warnings`.
+fn line_count_table(walked: &[(&PathBuf, Vec<PathBuf>)], args: &Cli, warnings: &mut Vec<Warning>) -> String {
+    let mut languages: BTreeMap<&'static str, LineCounts> = BTreeMap::new();
+    for path in walked.iter().flat_map(|(_, files)| files) {
+        if let Some(handler) = handlers_for(path, args).first()
+            && let Some(counts) = count_lines(path, handler, args.encoding, warnings)
+        {
+            languages.entry(handler.name()).or_default().add(&counts);
+        }
+    }
+    let mut total = LineCounts::default();
+    for counts in languages.values() {
+        total.add(counts);
+    }
+    let mut rows: Vec<(&str, &LineCounts)> = languages.iter().map(|(name, counts)| (*name, counts)).collect();
+    rows.sort_by_key(|(_, counts)| std::cmp::Reverse(counts.code()));
+
+    let rule = "-".repeat(70);
+    let row = |name: &str, counts: &LineCounts| {
+        format!(
+            "{:<20}{:>10}{:>10}{:>10}{:>10}{:>10}\n",
+            name,
+            counts.files,
+            counts.lines,
+            counts.blank,
+            counts.comment,
+            counts.code()
+        )
+    };
+    let mut table = format!(
+        "{}\n{:<20}{:>10}{:>10}{:>10}{:>10}{:>10}\n{}\n",
+        rule, "Language", "files", "lines", "blank", "comment", "code", rule
+    );
+    for (name, counts) in rows {
+        table.push_str(&row(name, counts));
+    }
+    table.push_str(&format!("{}\n{}{}\n", rule, row("Total", &total), rule));
+    table
+}
+
+/// Runs the passes that apply to each section on its own, once it has its display path.
+/// Returns the number of redactions made.
+fn finish_section(section: &mut Section, args: &Cli, members: &[(PathBuf, String)]) -> usize {
+    section.crate_name = owning_crate(members, &section.path);
+    section.compact = args.compact;
+
+    if args.header_meta {
+        section.file_info = file_info(&section.path);
+    }
+
+    if args.collapse_blank_lines {
+        section.content = collapse_blank_lines(&section.content, fence_handler(section.fence).as_ref());
+    }
+
+    // Minified code keeps little indentation worth converting
+    let unminified = if section.fence == "rust" { args.rust_minify_level() != MinifyLevel::Full } else { args.no_minify };
+    if args.respect_editorconfig
+        && unminified
+        && !section.summarized
+        && let Some(content) = editorconfig::reindent(&section.path, &section.content)
+    {
+        section.content = content;
+    }
+
+    if let Some(width) = args.wrap
+        && !section.summarized
+    {
+        section.content = wrap_lines(&section.content, width, &continuation_marker(section.fence));
+    }
+
+    if args.line_numbers && !section.summarized {
+        section.content = number_lines(&section.content);
+    }
+
+    // .env files are mostly secrets, so they're masked even without --redact
+    let mut redactions = 0;
+    if args.redact || section.fence == "dotenv" {
+        let (content, count) = redact::redact(&section.content);
+        section.content = content;
+        redactions = count;
+    }
+
+    // Counts reflect the final content, after every pass that changes it
+    if args.per_file_stats {
+        section.content_stats = Some((section.content.lines().count(), estimate_tokens(&section.content)));
+    }
+
+    redactions
+}
+
+/// Processes `path`, serving it from `cache` when unchanged.
+fn visit_path(
+    path: &Path,
+    args: &Cli,
+    cache: Option<&Cache>,
+    anonymizer: Option<&Anonymizer>,
+    sections: &mut Vec<Section>,
+    warnings: &mut Vec<Warning>,
+) -> anyhow::Result<()> {
+    if let Some((cached, cached_warnings)) = cache.and_then(|cache| cache.get(path, args)) {
+        debug!("Using the cached result for {}", path.display());
+        sections.extend(cached);
+        warnings.extend(cached_warnings);
+        return Ok(());
+    }
+    let (first_section, first_warning) = (sections.len(), warnings.len());
+    process_path(path, args, anonymizer, sections, warnings)?;
+    if let Some(cache) = cache {
+        cache.put(path, args, &sections[first_section..], &warnings[first_warning..]);
+    }
+    Ok(())
+}
+
+/// How a matched file is turned into a section.
+enum Handler {
+    Rust,
+    JavaScript,
+    /// Jupyter notebook, converted to a Python script
+    Notebook,
+    /// Any language in `LANGUAGES`
+    Generic(&'static LanguageSpec),
+    /// `--concat`: any text file, included exactly as written
+    Raw,
+}
+
+impl Handler {
+    fn name(&self) -> &'static str {
+        match self {
+            Handler::Rust => "rust",
+            Handler::JavaScript => "javascript",
+            Handler::Notebook => "python",
+            Handler::Generic(spec) => spec.name,
+            Handler::Raw => "text",
+        }
+    }
+}
+
+/// Every handler that claims `path` given the enabled language flags, in output order.
+fn handlers_for(path: &Path, args: &Cli) -> Vec<Handler> {
+    let extension = path.extension().and_then(|s| s.to_str());
+    let mut handlers = Vec::new();
+
+    if extension.is_some_and(|extension| extension_listed(&args.exclude_ext, extension)) {
+        return handlers;
+    }
+    // An allowlist replaces the language flags: listed extensions are processed, others never are
+    let allowlisted = !args.only_ext.is_empty();
+    if allowlisted && !extension.is_some_and(|extension| extension_listed(&args.only_ext, extension)) {
+        return handlers;
+    }
+    if args.concat {
+        return vec![Handler::Raw];
+    }
+    if let Some((_, language)) = extension.and_then(|extension| {
+        args.map_ext
+            .iter()
+            .rfind(|(mapped, _)| mapped.eq_ignore_ascii_case(extension))
+    }) {
+        return language_handler(language).into_iter().collect();
+    }
+    let all = args.all || allowlisted;
+
+    // Rust files are processed unless explicitly turned off
+    if !args.no_rust && extension == Some("rs") {
+        handlers.push(Handler::Rust);
+    }
+    if (args.javascript || all) && extension == Some("js") {
+        handlers.push(Handler::JavaScript);
+    }
+    for spec in LANGUAGES {
+        if (all || (spec.enabled)(args)) && spec.matches(path) && !defers_to_other_language(spec, extension, args, all) {
+            handlers.push(Handler::Generic(spec));
+        }
+    }
+    // Cargo.toml is described by --with-deps instead
+    if path.file_name().is_some_and(|name| name != "Cargo.toml") {
+        for spec in CONFIG_FORMATS {
+            if (allowlisted || (spec.enabled)(args)) && spec.matches(path) {
+                handlers.push(Handler::Generic(spec));
+            }
+        }
+    }
+    if (args.notebook || allowlisted) && extension == Some("ipynb") {
+        handlers.push(Handler::Notebook);
+    }
+
+    // Extensionless scripts are recognized by their interpreter
+    if handlers.is_empty() && extension.is_none() {
+        match shebang_interpreter(path).as_deref() {
+            Some("node") if args.javascript || args.all => handlers.push(Handler::JavaScript),
+            Some(interpreter) => {
+                let language = match interpreter {
+                    "sh" | "bash" | "zsh" | "dash" | "ksh" => "bash",
+                    other => other,
+                };
+                if let Some(spec) = LANGUAGES
+                    .iter()
+                    .find(|spec| spec.name == language && (args.all || (spec.enabled)(args)))
+                {
+                    handlers.push(Handler::Generic(spec));
+                }
+            }
+            None => {}
+        }
+    }
+
+    handlers
+}
+
+/// Picks one owner for extensions two languages claim, by which flags were given: `.m` is
+/// Objective-C with `--objc` (or just `-a`) and MATLAB with `-m`, and `.h` is C unless only
+/// `--objc` is given.
+fn defers_to_other_language(spec: &LanguageSpec, extension: Option<&str>, args: &Cli, all: bool) -> bool {
+    match (spec.name, extension) {
+        // An explicit `--objc` wins over `-m`, and `-a` alone means Objective-C, since MATLAB
+        // code is the rarer of the two in projects mixing languages
+        ("matlab", Some("m")) => args.objc || (all && !args.matlab),
+        // `-m` without `--objc` leaves `.m` to MATLAB even under `-a`
+        ("objc", Some("m")) => !args.objc && args.matlab,
+        // Headers are far more often C or C++, so they only go to Objective-C when C isn't
+        // being collected too
+        ("objc", Some("h")) => args.cpp || all,
+        _ => false,
+    }
+}
+
+/// The handler for a language name from `canonical_language`.
+fn language_handler(language: &str) -> Option<Handler> {
+    match language {
+        "rust" => Some(Handler::Rust),
+        "javascript" => Some(Handler::JavaScript),
+        _ => LANGUAGES
+            .iter()
+            .chain(CONFIG_FORMATS)
+            .find(|spec| spec.name == language)
+            .map(Handler::Generic),
+    }
+}
+
+/// Context windows, in tokens, of models `--model` knows about.
+const MODEL_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4o-mini", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o3-mini", 200_000),
+    ("o4-mini", 200_000),
+    ("claude-3-haiku", 200_000),
+    ("claude-3-opus", 200_000),
+    ("claude-3-5-sonnet", 200_000),
+    ("claude-3-7-sonnet", 200_000),
+    ("claude-sonnet-4", 200_000),
+    ("claude-opus-4", 200_000),
+    ("gemini-1.5-flash", 1_048_576),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-2.0-flash", 1_048_576),
+    ("gemini-2.5-pro", 1_048_576),
+    ("llama-3.1", 131_072),
+    ("mistral-large", 131_072),
+];
+
+/// Parses a `--model` value into the model's name and context window.
+fn parse_model(value: &str) -> Result<(&'static str, usize), String> {
+    MODEL_CONTEXT_WINDOWS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value.trim()))
+        .copied()
+        .ok_or_else(|| {
+            let known: Vec<&str> = MODEL_CONTEXT_WINDOWS.iter().map(|(name, _)| *name).collect();
+            format!("unknown model `{}`; known models are {}", value, known.join(", "))
+        })
+}
+
+/// Parses a `--map-ext` value such as `inc=php` or `.txt=sql`.
+fn parse_extension_mapping(value: &str) -> Result<(String, &'static str), String> {
+    let (extension, language) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected EXT=LANGUAGE, got `{}`", value))?;
+    let language = canonical_language(language).ok_or_else(|| format!("unknown language `{}`", language))?;
+    Ok((extension.trim_start_matches('.').to_string(), language))
+}
+
+/// True when `extension` is in a user-supplied list, ignoring case and leading dots.
+fn extension_listed(list: &[String], extension: &str) -> bool {
+    list.iter()
+        .any(|listed| listed.trim_start_matches('.').eq_ignore_ascii_case(extension))
+}
+
+/// Fence label for `path` from the language tables, or none for unknown file types.
+fn guess_fence(path: &Path) -> &'static str {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("rs") => "rust",
+        Some("js") => "javascript",
+        _ => LANGUAGES
+            .iter()
+            .chain(CONFIG_FORMATS)
+            .find(|spec| spec.matches(path))
+            .map_or("", |spec| spec.fence),
+    }
+}
+
+/// Larger extensionless files are assumed to be data rather than scripts.
+const MAX_SCRIPT_SIZE: u64 = 1024 * 1024;
+
+/// Reads the interpreter named by a `#!` line, e.g. `python` for `#!/usr/bin/env python3`.
+fn shebang_interpreter(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_SCRIPT_SIZE {
+        return None;
+    }
+    let mut first_line = String::new();
+    BufReader::new(fs::File::open(path).ok()?)
+        .take(512)
+        .read_line(&mut first_line)
+        .ok()?;
+
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    // `env` runs the first argument that isn't an option
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    // Drop version suffixes like `python3.12`
+    Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.').to_string())
+}
+
+/// Processes a single file with every language handler that claims it, appending the
+/// resulting sections.
+fn process_path(
+    path: &Path,
+    args: &Cli,
+    anonymizer: Option<&Anonymizer>,
+    sections: &mut Vec<Section>,
+    warnings: &mut Vec<Warning>,
+) -> anyhow::Result<()> {
+    // Very large files get a structural summary instead of their full body
+    let too_large = args
+        .summarize_large
+        .is_some_and(|limit| fs::metadata(path).is_ok_and(|m| m.len() > limit));
+
+    for handler in handlers_for(path, args) {
+        // Binary files are skipped without a warning
+        if matches!(handler, Handler::Raw) && looks_binary(path) {
+            continue;
+        }
+        let Some(mut source) = read_source(path, args.encoding, warnings) else {
+            continue;
+        };
+        // Notebooks are converted to a Python script and then handled like any other Python file
+        if matches!(handler, Handler::Notebook) {
+            match notebook_to_python(&source) {
+                Ok(script) => source = script,
+                Err(e) => {
+                    warnings.push(Warning::new(path, WarningKind::Parse, e));
+                    continue;
+                }
+            }
+        }
+        let source_lines = source.lines().count();
+
+        // `--head`/`--tail` and `--marker` pick lines out of the source, before any processing
+        let mut ranges = selected_lines(&source, args);
+        if args.marker.is_some() && ranges.as_ref().is_some_and(Vec::is_empty) {
+            info!("Skipping {}: no line matches --marker", path.display());
+            continue;
+        }
+        let summarize = too_large && ranges.is_none() && !matches!(handler, Handler::Raw);
+        if summarize {
+            info!("Summarizing {}: larger than --summarize-large", path.display());
+            let limit = args.summarize_large.unwrap_or_default();
+            warnings.push(Warning::new(path, WarningKind::TooLarge, format!("over {} bytes, summarized", limit)));
+        }
+        if let Some(ranges) = &ranges
+            && args.marker.is_none()
+        {
+            let kept: usize = ranges.iter().map(|range| range.len()).sum();
+            let message = format!("{} of {} lines left out by --head/--tail", source_lines - kept, source_lines);
+            warnings.push(Warning::new(path, WarningKind::TooLarge, message));
+        }
+
+        let (fence, symbols, content) = match handler {
+            Handler::Rust => {
+                let symbols = if args.outline.is_some() {
+                    symbols::rust_outline(&source)
+                } else if args.symbols {
+                    symbols::symbols("rust", path, &source)
+                } else {
+                    None
+                };
+                // A file that doesn't parse as a whole won't in pieces either, and one warning
+                // about it is enough
+                let mut piece_warnings = Vec::new();
+                let piece_warnings = match &mut ranges {
+                    Some(ranges) => match widen_to_rust_items(&source, ranges) {
+                        Ok(()) => &mut *warnings,
+                        Err(e) => {
+                            warnings.push(Warning::new(path, WarningKind::Parse, e));
+                            &mut piece_warnings
+                        }
+                    },
+                    None => &mut *warnings,
+                };
+                let content = if args.outline == Some(Outline::Only) && symbols.is_some() {
+                    String::new()
+                } else if summarize {
+                    summary::summarize_rust(&source, args.strips_docs("rust"))
+                } else {
+                    process_ranges(&source, ranges.as_deref(), args, |code| {
+                        if args.keep_license {
+                            let (license, body) = split_license_header(code, &["//"], &[("/*", "*/")]);
+                            let minified = process_rust_file(path, body.to_string(), args, anonymizer, piece_warnings);
+                            format!("{}{}", license, minified)
+                        } else {
+                            process_rust_file(path, code.to_string(), args, anonymizer, piece_warnings)
+                        }
+                    })
+                };
+                ("rust", symbols, content)
+            }
+
+            Handler::JavaScript => {
+                let content = if summarize {
+                    summary::summarize_lines(&source, &["//"])
+                } else {
+                    let strip_docs = args.strips_docs("javascript");
+                    let mut error = None;
+                    let content = process_ranges(&source, ranges.as_deref(), args, |code| {
+                        let (license, body) = if args.keep_license {
+                            split_license_header(code, &["//"], &[("/*", "*/")])
                         } else {
-                            file_contents
+                            ("", code)
                         };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```bash\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
-                    }
-                    
-                    // Lua
-                    if (args.lua || args.all)
-                        && (path.extension().and_then(|s| s.to_str()) == Some("lua"))
-                    {
-                        let file_contents = fs::read_to_string(path)?;
-                        let _default_skip_dirs = vec!["bin".to_string()];
-                        let line_comment = "--".to_string();
-                        let block_comment_start = "--[[".to_string();
-                        let block_comment_end = "]]".to_string();
-                    
-                        let stripped = if args.remove_docs {
-                            remove_documentation(
-                                &file_contents,
-                                &line_comment,
-                                &block_comment_start,
-                                &block_comment_end,
-                            )
+                        // Lines cut out of a file seldom parse on their own, so they get the
+                        // token-aware text minifier instead
+                        let minified = if ranges.is_some() && !args.no_minify {
+                            Ok(typescript::minify(body, strip_docs))
                         } else {
-                            file_contents
+                            process_javascript_file(body, strip_docs, args.no_minify)
                         };
-                    
-                        let minified = remove_whitespace(&stripped);
-                    
-                        markdown_output.push_str(&format!(
-                            "## {}\n```lua\n{}\n```\n",
-                            path.display(),
-                            minified
-                        ));
+                        match minified {
+                            Ok(minified) => format!("{}{}", license, minified),
+                            Err(e) => {
+                                error.get_or_insert(e);
+                                String::new()
+                            }
+                        }
+                    });
+                    if let Some(e) = error {
+                        warnings.push(Warning::new(path, WarningKind::Parse, e));
+                        continue;
                     }
-                    
+                    content
+                };
+                let symbols = if args.symbols {
+                    symbols::symbols("javascript", path, &source)
+                } else {
+                    None
+                };
+                ("javascript", symbols, content)
+            }
+
+            // Every other language goes through the generic comment-strip + whitespace pipeline
+            Handler::Generic(_) | Handler::Notebook => {
+                let spec = match handler {
+                    Handler::Generic(spec) => spec,
+                    _ => LANGUAGES
+                        .iter()
+                        .find(|spec| spec.name == "python")
+                        .expect("python is a built-in language"),
+                };
+                let symbols = if args.symbols {
+                    symbols::symbols(spec.name, path, &source)
+                } else {
+                    None
+                };
+                let content = if summarize {
+                    summary::summarize_lines(&source, spec.line_comments)
+                } else {
+                    process_ranges(&source, ranges.as_deref(), args, |code| process_source(spec, code, args))
+                };
+                (spec.fence, symbols, content)
+            }
+
+            Handler::Raw => (guess_fence(path), None, process_ranges(&source, ranges.as_deref(), args, str::to_string)),
+        };
+
+        sections.push(Section {
+            path: path.to_path_buf(),
+            display_path: String::new(),
+            fence,
+            symbols,
+            content,
+            summarized: summarize,
+            crate_name: None,
+            file_info: None,
+            content_stats: None,
+            compact: false,
+            duplicate_of: None,
+            source_lines,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads `path` as UTF-8 text, recording a warning instead when it can't be. The text is
+/// passed through `normalize_text`.
+fn read_source(path: &Path, encoding: Encoding, warnings: &mut Vec<Warning>) -> Option<String> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warnings.push(Warning::new(path, WarningKind::Unreadable, e));
+            return None;
+        }
+    };
+    let source = match (String::from_utf8(bytes), encoding) {
+        (Ok(source), _) => source,
+        (Err(e), Encoding::Utf8) => {
+            warnings.push(Warning::new(path, WarningKind::NotUtf8, e));
+            return None;
+        }
+        (Err(e), Encoding::Lossy) => {
+            warnings.push(Warning::new(path, WarningKind::Lossy, &e));
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+        (Err(e), Encoding::Latin1) => {
+            let (source, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(e.as_bytes());
+            source.into_owned()
+        }
+    };
+    Some(normalize_text(source))
+}
+
+/// Whether `path` holds binary data rather than text, judged like git does: by a NUL byte in
+/// the first 8000 bytes.
+fn looks_binary(path: &Path) -> bool {
+    let mut start = Vec::new();
+    fs::File::open(path).and_then(|file| file.take(8000).read_to_end(&mut start)).is_ok_and(|_| start.contains(&0))
+}
+
+/// The lines of `source` kept by `--marker`, each match with `--context` lines on either
+/// side, or by `--head`/`--tail`, as ranges of 0-based line indices in order. `None` keeps
+/// the whole file, as does `--head`/`--tail` for files no longer than that.
+fn selected_lines(source: &str, args: &Cli) -> Option<Vec<Range<usize>>> {
+    let lines: Vec<&str> = source.lines().collect();
+    if let Some(marker) = &args.marker {
+        let mut windows: Vec<Range<usize>> = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if !marker.is_match(line) {
+                continue;
+            }
+            let window = i.saturating_sub(args.context)..(i + args.context + 1).min(lines.len());
+            // Overlapping and adjacent windows are merged
+            match windows.last_mut() {
+                Some(last) if window.start <= last.end => last.end = window.end,
+                _ => windows.push(window),
+            }
+        }
+        return Some(windows);
+    }
+
+    if args.head.is_none() && args.tail.is_none() {
+        return None;
+    }
+    let (head, tail) = (args.head.unwrap_or(0), args.tail.unwrap_or(0));
+    if lines.len() <= head + tail {
+        return None;
+    }
+    Some(
+        [0..head, lines.len() - tail..lines.len()]
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect(),
+    )
+}
+
+/// Runs `process` over all of `source`, or over each of `ranges` from `selected_lines` on its
+/// own, joined by lines marking what was left out: `...` between `--marker` windows, and
+/// `... N lines omitted ...` wherever `--head`/`--tail` cut.
+fn process_ranges(
+    source: &str,
+    ranges: Option<&[Range<usize>]>,
+    args: &Cli,
+    mut process: impl FnMut(&str) -> String,
+) -> String {
+    let Some(ranges) = ranges else {
+        return process(source);
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let omitted = |count: usize| format!("... {} lines omitted ...", count);
+    let mut parts = Vec::new();
+    let mut next = 0;
+    for range in ranges {
+        if range.start > next {
+            if args.marker.is_none() {
+                parts.push(omitted(range.start - next));
+            } else if next > 0 {
+                parts.push("...".to_string());
+            }
+        }
+        let piece = format!("{}\n", lines[range.clone()].join("\n"));
+        parts.push(process(&piece).trim_end_matches('\n').to_string());
+        next = range.end;
+    }
+    if args.marker.is_none() && next < lines.len() {
+        parts.push(omitted(lines.len() - next));
+    }
+    parts.join("\n")
+}
+
+/// Drops a leading byte order mark and turns `\r\n` and lone `\r` line endings into `\n`.
+fn normalize_text(mut source: String) -> String {
+    if source.starts_with('\u{feff}') {
+        source.drain(..'\u{feff}'.len_utf8());
+    }
+    if source.contains('\r') {
+        source = source.replace("\r\n", "\n").replace('\r', "\n");
+    }
+    source
+}
+
+/// Why a file was left out or only partly processed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum WarningKind {
+    /// Included as raw source (Rust) or left out (JavaScript, notebooks)
+    Parse,
+    /// Included with invalid bytes replaced (`--encoding lossy`)
+    Lossy,
+    NotUtf8,
+    Unreadable,
+    /// Summarized by `--summarize-large` or cut by `--head`/`--tail`
+    TooLarge,
+    /// `--filter-cmd` failed, so the content is included unfiltered
+    Filter,
+}
+
+impl WarningKind {
+    fn description(self) -> &'static str {
+        match self {
+            WarningKind::Parse => "could not be parsed (Rust files are included unminified)",
+            WarningKind::Lossy => "are not UTF-8 text; invalid bytes were replaced",
+            WarningKind::NotUtf8 => "are not UTF-8 text and were skipped",
+            WarningKind::Unreadable => "could not be read and were skipped",
+            WarningKind::TooLarge => "are too large and were only partly included",
+            WarningKind::Filter => "failed --filter-cmd and were included unfiltered",
+        }
+    }
+}
+
+/// A problem with one file, reported with the others once the walk is done.
+struct Warning {
+    path: PathBuf,
+    kind: WarningKind,
+    message: String,
+}
+
+impl Warning {
+    fn new(path: &Path, kind: WarningKind, error: impl std::fmt::Display) -> Self {
+        Warning {
+            path: path.to_path_buf(),
+            kind,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Prints all warnings to stderr, grouped by kind.
+fn print_warnings(warnings: &[Warning]) {
+    let mut by_kind: BTreeMap<WarningKind, Vec<&Warning>> = BTreeMap::new();
+    for warning in warnings {
+        by_kind.entry(warning.kind).or_default().push(warning);
+    }
+    for (kind, warnings) in by_kind {
+        eprintln!("{} file(s) {}:", warnings.len(), kind.description());
+        for warning in warnings {
+            eprintln!("  {}: {}", warning.path.display(), warning.message);
+        }
+    }
+}
+
+/// Runs source through the generic pipeline for `spec`: optional license split, comment
+/// stripping, and whitespace minification.
+fn process_source(spec: &LanguageSpec, file_contents: &str, args: &Cli) -> String {
+    // A leading license header is set aside so neither stripping nor minifying touches it
+    let (license, body) = if args.keep_license {
+        split_license_header(file_contents, spec.line_comments, spec.block_comments)
+    } else {
+        ("", file_contents)
+    };
+
+    // TypeScript has its own token-aware pass, since template and regex literals trip up
+    // the generic one
+    let minified = if spec.name == "typescript" && !args.no_minify {
+        typescript::minify(body, args.strips_docs(spec.name))
+    } else if spec.name == "yaml" && (args.strips_docs(spec.name) || !args.no_minify) {
+        yaml::minify(body, args.strips_docs(spec.name), !args.no_minify)
+    } else {
+        let stripped = if args.strips_docs(spec.name) {
+            let body = match spec.column_comments {
+                Some((column, markers)) => remove_column_comments(body, column, markers),
+                None => body.to_string(),
+            };
+            if spec.name == "batch" {
+                remove_batch_comments(&body)
+            } else {
+                remove_documentation(
+                    &body,
+                    spec.line_comments,
+                    spec.block_comments,
+                    spec.nested_block_comments,
+                    RawStrings::of(spec.name),
+                )
+            }
+        } else {
+            body.to_string()
+        };
+
+        if args.no_minify || !spec.minify {
+            stripped
+        } else if spec.preserve_newlines {
+            remove_whitespace_within_lines(&stripped, spec.name == "make")
+        } else {
+            remove_whitespace(&stripped, RawStrings::of(spec.name), spec.js_literals)
+        }
+    };
+
+    format!("{}{}", license, minified)
+}
+
+/// Extracts the code cells of a Jupyter notebook as one Python script. Markdown cells are
+/// kept as `#` comments, so `--remove-docs` drops them along with other comments.
+fn notebook_to_python(json: &str) -> anyhow::Result<String> {
+    let notebook: serde_json::Value = serde_json::from_str(json)?;
+    let cells = notebook
+        .get("cells")
+        .and_then(|cells| cells.as_array())
+        .ok_or_else(|| anyhow::anyhow!("notebook has no cells"))?;
+
+    let mut script = String::new();
+    for cell in cells {
+        // `source` is either a single string or a list of lines
+        let source = match cell.get("source") {
+            Some(serde_json::Value::String(source)) => source.clone(),
+            Some(serde_json::Value::Array(lines)) => lines.iter().filter_map(|line| line.as_str()).collect(),
+            _ => continue,
+        };
+        match cell.get("cell_type").and_then(|t| t.as_str()) {
+            Some("code") => script.push_str(&source),
+            Some("markdown") => {
+                for line in source.lines() {
+                    script.push_str(format!("# {}", line).trim_end());
+                    script.push('\n');
                 }
             }
-            Err(e) => {
-                // If there's an error reading a directory entry, just print it
-                eprintln!("Error reading directory entry: {}", e);
+            _ => continue,
+        }
+        if !script.ends_with('\n') {
+            script.push('\n');
+        }
+        script.push('\n');
+    }
+
+    Ok(script)
+}
+
+/// One processed file, kept separate from the final document so whole files can be
+/// included or left out.
+struct Section {
+    path: PathBuf,
+    /// Path shown in the header, relative to the walked directory
+    display_path: String,
+    /// Label placed after the opening code fence
+    fence: &'static str,
+    symbols: Option<Vec<String>>,
+    content: String,
+    /// Content is a structural summary rather than the whole file
+    summarized: bool,
+    /// Workspace member the file belongs to, when walking a Cargo workspace
+    crate_name: Option<String>,
+    /// Size and modification date shown in the header with `--header-meta`
+    file_info: Option<String>,
+    /// Line and token counts of the emitted content with `--per-file-stats`
+    content_stats: Option<(usize, usize)>,
+    /// With `--dedupe`, the display path of an earlier file with identical content, which
+    /// stands in for this one
+    duplicate_of: Option<String>,
+    /// Line count of the original file, before any processing
+    source_lines: usize,
+    /// With `--compact`, the path goes in the code fence's info string instead of a heading
+    compact: bool,
+}
+
+impl Section {
+    /// The `<!-- lines: .., tokens: .. -->` line for `--per-file-stats`, or nothing.
+    fn stats_comment(&self) -> String {
+        match self.content_stats {
+            Some((lines, tokens)) => format!("<!-- lines: {}, tokens: {} -->\n", lines, tokens),
+            None => String::new(),
+        }
+    }
+
+    fn render(&self, format: Format) -> String {
+        match format {
+            Format::Markdown => self.to_markdown(),
+            Format::Xml => self.to_xml(),
+            Format::Jsonl => self.to_json_line(),
+        }
+    }
+
+    fn to_json_line(&self) -> String {
+        let mut record = serde_json::json!({
+            "path": self.display_path,
+            "language": self.fence,
+            "content": self.content,
+        });
+        let optional = [
+            ("info", self.file_info.clone().map(serde_json::Value::from)),
+            ("crate", self.crate_name.clone().map(serde_json::Value::from)),
+            ("summarized", self.summarized.then_some(serde_json::Value::Bool(true))),
+            ("symbols", self.symbols.clone().map(serde_json::Value::from)),
+            ("same_as", self.duplicate_of.clone().map(serde_json::Value::from)),
+            ("lines", self.content_stats.map(|(lines, _)| serde_json::Value::from(lines))),
+            ("tokens", self.content_stats.map(|(_, tokens)| serde_json::Value::from(tokens))),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                record[name] = value;
+            }
+        }
+        format!("{}\n", record)
+    }
+
+    fn to_xml(&self) -> String {
+        let mut attributes = format!("path=\"{}\"", xml_attribute(&self.display_path));
+        if let Some(original) = &self.duplicate_of {
+            return format!("<file {} same-as=\"{}\"/>\n", attributes, xml_attribute(original));
+        }
+        let optional = [
+            ("lang", Some(self.fence).filter(|fence| !fence.is_empty()).map(str::to_string)),
+            ("info", self.file_info.clone()),
+            ("crate", self.crate_name.clone()),
+            ("summarized", self.summarized.then(|| "true".to_string())),
+            ("symbols", self.symbols.as_ref().map(|symbols| symbols.join(", "))),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                attributes.push_str(&format!(" {}=\"{}\"", name, xml_attribute(&value)));
             }
         }
+        format!(
+            "<file {}>\n{}{}\n</file>\n",
+            attributes,
+            // Already an XML comment
+            self.stats_comment(),
+            xml_text(&self.content)
+        )
+    }
+
+    fn to_markdown(&self) -> String {
+        let crate_label = match &self.crate_name {
+            Some(name) => format!(" (crate: {})", name),
+            None => String::new(),
+        };
+        let file_info = match &self.file_info {
+            Some(info) => format!(" ({})", info),
+            None => String::new(),
+        };
+        // `--compact` names files in code spans where there's no fence to carry the path
+        let title = if self.compact {
+            format!("`{}`{}{}:", self.display_path, file_info, crate_label)
+        } else {
+            format!("## {}{}{}", self.display_path, file_info, crate_label)
+        };
+        if let Some(original) = &self.duplicate_of {
+            return format!("{}\nSame content as `{}`.\n", title, original);
+        }
+        // `--outline=only`
+        if self.content.is_empty() && self.symbols.is_some() {
+            return format!(
+                "{}\n{}{}",
+                title,
+                self.stats_comment(),
+                format_symbols(self.symbols.as_deref())
+            );
+        }
+        // The fence must be longer than any run of backticks inside the content
+        let longest_run = self
+            .content
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest_run.max(2) + 1);
+        let summarized = if self.summarized { " (summarized)" } else { "" };
+        if self.compact {
+            return format!(
+                "{}{}{}{} {}{}{}{}\n{}\n{}\n",
+                self.stats_comment(),
+                format_symbols(self.symbols.as_deref()),
+                fence,
+                self.fence,
+                self.display_path,
+                file_info,
+                crate_label,
+                summarized,
+                self.content,
+                fence
+            );
+        }
+        format!(
+            "## {}{}{}{}\n{}{}{}{}\n{}\n{}\n",
+            self.display_path,
+            file_info,
+            crate_label,
+            summarized,
+            self.stats_comment(),
+            format_symbols(self.symbols.as_deref()),
+            fence,
+            self.fence,
+            self.content,
+            fence
+        )
+    }
+}
+
+/// Escapes text for use as XML character data. `>` only needs escaping in `]]>`, and
+/// leaving it alone keeps code like `->` readable.
+fn xml_text(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace("]]>", "]]&gt;")
+}
+
+/// Escapes text for use inside a double-quoted XML attribute.
+fn xml_attribute(text: &str) -> String {
+    xml_text(text).replace('"', "&quot;")
+}
+
+/// Describes a file's size and modification date, e.g. `4.2 KiB, modified 2024-01-02`.
+fn file_info(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = humantime::format_rfc3339_seconds(metadata.modified().ok()?).to_string();
+    Some(format!(
+        "{}, modified {}",
+        human_size(metadata.len()),
+        modified.get(..10)?
+    ))
+}
+
+/// Formats a byte count with a binary unit, e.g. `512 B` or `4.2 KiB`.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Heading for a group of files sharing a fence label, e.g. `Rust` for `rust`.
+fn language_heading(fence: &str) -> String {
+    let mut chars = fence.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Other".to_string(),
+    }
+}
+
+/// Rough token estimate (about four characters per token for code and English text).
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Keeps each section, in walk order, that still fits within `budget` tokens as rendered in
+/// `format`, skipping the ones that don't. Then drops kept sections from the end until the
+/// note about omitted files fits too. `reserved` is the cost of everything else in the
+/// document, such as the header. Returns the omitted paths in walk order.
+fn apply_token_budget(sections: &mut Vec<Section>, reserved: usize, budget: usize, format: Format) -> Vec<String> {
+    let mut used = reserved + estimate_tokens(format.closing());
+    let mut included = Vec::new();
+    let mut omitted = Vec::new();
+
+    // Greedily keep every file that still fits, skipping over ones that don't
+    for (position, section) in sections.drain(..).enumerate() {
+        let tokens = estimate_tokens(&section.render(format));
+        if used + tokens <= budget {
+            used += tokens;
+            included.push((position, tokens, section));
+        } else {
+            omitted.push((position, section.display_path));
+        }
+    }
+
+    // The omitted-files note itself costs tokens, so make room for it
+    let note_tokens = |omitted: &[(usize, String)]| {
+        let paths: Vec<String> = omitted.iter().map(|(_, path)| path.clone()).collect();
+        estimate_tokens(&format.block("omitted", &omitted_note(&paths, budget)))
+    };
+    while !omitted.is_empty() && used + note_tokens(&omitted) > budget {
+        match included.pop() {
+            Some((position, tokens, section)) => {
+                used -= tokens;
+                omitted.push((position, section.display_path));
+            }
+            None => break,
+        }
+    }
+
+    omitted.sort_by_key(|&(position, _)| position);
+    *sections = included.into_iter().map(|(_, _, section)| section).collect();
+    omitted.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Running totals for the `## Stats` footer.
+#[derive(Default)]
+struct Stats {
+    files_per_language: BTreeMap<&'static str, usize>,
+    files: usize,
+    source_lines: usize,
+    bytes: usize,
+}
+
+impl Stats {
+    fn add(&mut self, section: &Section) {
+        let language = if section.fence.is_empty() { "other" } else { section.fence };
+        *self.files_per_language.entry(language).or_default() += 1;
+        self.files += 1;
+        self.source_lines += section.source_lines;
+        self.bytes += section.content.len();
+    }
+
+    /// Lists files per language, total lines before minification, bytes after, and the
+    /// estimated token count of the whole prompt.
+    fn footer(&self, tokens: usize) -> String {
+        let mut footer = String::from("## Stats\n");
+        for (language, files) in &self.files_per_language {
+            footer.push_str(&format!("- {}: {} file(s)\n", language, files));
+        }
+        footer.push_str(&format!(
+            "- Total: {} file(s), {} lines before minification, {} bytes after, ~{} tokens\n",
+            self.files, self.source_lines, self.bytes, tokens
+        ));
+        footer
+    }
+}
+
+/// Lists files left out to respect the token budget, or nothing when all files fit.
+fn omitted_note(omitted: &[String], budget: usize) -> String {
+    if omitted.is_empty() {
+        return String::new();
+    }
+    let mut note = format!(
+        "## Omitted files\n{} file(s) were left out to stay within the {}-token budget:\n",
+        omitted.len(),
+        budget
+    );
+    for path in omitted {
+        note.push_str(&format!("- {}\n", path));
+    }
+    note
+}
+
+/// Groups sections by the first component of their display path, for `--split-by-dir`.
+/// Files directly in the walked directory go under `root`.
+fn group_by_directory(sections: &[Section]) -> BTreeMap<String, Vec<&Section>> {
+    let mut groups: BTreeMap<String, Vec<&Section>> = BTreeMap::new();
+    for section in sections {
+        let group = match section.display_path.split_once('/') {
+            Some((first, _)) if !first.is_empty() && first != ".." => first.to_string(),
+            _ => "root".to_string(),
+        };
+        groups.entry(group).or_default().push(section);
+    }
+    groups
+}
+
+/// Packs sections in order into documents that each stay within `budget` tokens. A file
+/// larger than the budget on its own gets a document to itself.
+fn chunk_sections(sections: &[Section], header: &str, budget: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = header.to_string();
+    let mut used = estimate_tokens(header);
+    let mut has_sections = false;
+
+    for section in sections {
+        let rendered = section.to_markdown();
+        let tokens = estimate_tokens(&rendered);
+        if has_sections && used + tokens > budget {
+            chunks.push(std::mem::replace(&mut current, header.to_string()));
+            used = estimate_tokens(header);
+        }
+        if estimate_tokens(header) + tokens > budget {
+            eprintln!(
+                "Warning: {} (~{} tokens) exceeds the budget on its own",
+                section.display_path,
+                tokens
+            );
+        }
+        current.push_str(&rendered);
+        used += tokens;
+        has_sections = true;
+    }
+
+    if has_sections {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Builds the `## Contents` list for `--toc`, linking each file to its heading with the
+/// anchor GitHub would generate for it.
+fn table_of_contents(header: &str, sections: &[Section], grouped: bool) -> String {
+    // Anchors are numbered in document order, so every heading before a file's counts
+    let mut anchors = Anchors::default();
+    for line in header.lines().filter(|line| line.starts_with('#')) {
+        anchors.anchor(line.trim_start_matches('#'));
+    }
+    anchors.anchor("Contents");
+
+    let mut toc = String::from("## Contents\n");
+    for (i, section) in sections.iter().enumerate() {
+        if grouped && (i == 0 || sections[i - 1].fence != section.fence) {
+            anchors.anchor(&language_heading(section.fence));
+        }
+        let rendered = section.render(Format::Markdown);
+        let heading = rendered.lines().next().unwrap_or_default().trim_start_matches('#');
+        toc.push_str(&format!("- [{}](#{})\n", section.display_path, anchors.anchor(heading)));
+    }
+    toc.push('\n');
+    toc
+}
+
+/// GitHub's heading anchors: lowercased, punctuation dropped, spaces turned into hyphens,
+/// and `-1`, `-2`, ... added to repeats.
+#[derive(Default)]
+struct Anchors {
+    seen: HashSet<String>,
+}
+
+impl Anchors {
+    fn anchor(&mut self, heading: &str) -> String {
+        let base: String = heading
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+                _ => None,
+            })
+            .collect();
+        let mut anchor = base.clone();
+        let mut n = 0;
+        while !self.seen.insert(anchor.clone()) {
+            n += 1;
+            anchor = format!("{}-{}", base, n);
+        }
+        anchor
     }
+}
 
-    // Print the final markdown document to stdout
-    println!("# {}", project_name);
-    println!("{}", markdown_output);
+/// Renders a symbol list as a markdown bullet list, or nothing when there are no symbols.
+fn format_symbols(symbols: Option<&[String]>) -> String {
+    symbols
+        .unwrap_or_default()
+        .iter()
+        .map(|symbol| format!("- `{}`\n", symbol))
+        .collect()
+}
 
+/// Widens `ranges` from `selected_lines` to whole top-level items, such as functions and
+/// impl blocks with their attributes and doc comments, so each run of lines parses on its own.
+fn widen_to_rust_items(source: &str, ranges: &mut Vec<Range<usize>>) -> syn::Result<()> {
+    use syn::spanned::Spanned;
+
+    let file = syn::parse_file(source)?;
+    let items: Vec<Range<usize>> = file
+        .items
+        .iter()
+        .map(|item| {
+            let span = item.span();
+            span.start().line - 1..span.end().line
+        })
+        .collect();
+    for range in ranges.iter_mut() {
+        // Taking in one item can reach into the next when they share a line
+        while let Some(item) = items.iter().find(|item| {
+            item.start < range.end && range.start < item.end && (item.start < range.start || item.end > range.end)
+        }) {
+            range.start = range.start.min(item.start);
+            range.end = range.end.max(item.end);
+        }
+    }
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
     Ok(())
 }
 
-/// Reads a Rust file, optionally removes docs, minifies, and returns the minified string.
-fn process_rust_file(path: &Path, strip_docs: bool) -> anyhow::Result<String> {
-    let code = fs::read_to_string(path)?;
-    let ast = syn::parse_file(&code)?;
+/// Takes a Rust file's source, optionally removes docs, minifies, and returns the minified string.
+///
+/// At `MinifyLevel::None` (`--no-minify`), the original source is returned untouched, or
+/// pretty-printed from the AST when anything else changes it.
+fn process_rust_file(
+    path: &Path,
+    code: String,
+    args: &Cli,
+    anonymizer: Option<&Anonymizer>,
+    warnings: &mut Vec<Warning>,
+) -> String {
+    let strip_docs = args.strips_docs("rust");
+    let level = args.rust_minify_level();
+    // Nothing to strip, rename, or minify, so keep the file exactly as written.
+    if level == MinifyLevel::None && !strip_docs && !args.trim_imports && args.strip_attributes.is_none() && anonymizer.is_none() {
+        return code;
+    }
+
+    // Files using syntax `syn` doesn't understand (e.g. nightly features) are still worth
+    // including, so fall back to the raw source rather than dropping them.
+    let ast = match syn::parse_file(&code) {
+        Ok(ast) => ast,
+        Err(e) => {
+            warnings.push(Warning::new(path, WarningKind::Parse, e));
+            return if strip_docs {
+                remove_documentation(&code, &["//"], &[("/*", "*/")], true, RawStrings::Rust)
+            } else {
+                code
+            };
+        }
+    };
 
     // If the user wants to remove docs, do so before minifying.
-    let ast = if strip_docs {
+    let mut ast = if strip_docs {
         remove_docs(ast)
     } else {
         ast
     };
 
-    // Minify the AST into a single-string representation
-    let minified = minify_file(&ast);
+    // Only the file's own imports; those inside modules and functions stay
+    if args.trim_imports {
+        ast.items.retain(|item| !matches!(item, syn::Item::Use(_)));
+    }
 
-    Ok(minified)
+    if let Some(paths) = &args.strip_attributes {
+        attributes::strip(&mut ast, paths);
+    }
+
+    if let Some(anonymizer) = anonymizer {
+        anonymizer.rename(&mut ast);
+    }
+
+    match level {
+        // Reformat the AST into readable source rather than minifying it
+        MinifyLevel::None | MinifyLevel::Light => prettyplease::unparse(&ast),
+        // Minify the AST into a single-string representation
+        MinifyLevel::Full => minify_file(&ast),
+    }
 }
 
-/// Reads a javascript file, optionally removes docs, minifies, and returns the minified string.
-fn process_javascript_file(path: &Path, strip_docs: bool) -> anyhow::Result<String> {
-    let code = fs::read_to_string(path)?;
+/// Takes a javascript file's source, optionally removes docs, minifies, and returns the minified string.
+fn process_javascript_file(code: &str, strip_docs: bool, no_minify: bool) -> anyhow::Result<String> {
+    if no_minify {
+        return Ok(if strip_docs {
+            remove_documentation(code, &["//"], &[("/*", "*/")], false, RawStrings::None)
+        } else {
+            code.to_string()
+        });
+    }
 
     // If the user wants to remove docs, do so before minifying.
     if strip_docs {
@@ -883,7 +3976,7 @@ fn process_javascript_file(path: &Path, strip_docs: bool) -> anyhow::Result<Stri
     let mut out = Vec::new();
     
     // Minify the javascript into a single-string representation
-    minify(&session, TopLevelMode::Global, code.as_bytes(), &mut out).unwrap();
+    minify(&session, TopLevelMode::Global, code.as_bytes(), &mut out).map_err(|e| anyhow::anyhow!("{:?}", e))?;
 
     // Convert the resulting Vec<u8> to a String
     let minified = String::from_utf8(out)?;
@@ -891,141 +3984,667 @@ fn process_javascript_file(path: &Path, strip_docs: bool) -> anyhow::Result<Stri
     Ok(minified)
 }
 
+/// Pipes `content` through the `--filter-cmd` shell command and returns what it prints. The
+/// file's path is passed in `CARGO_PROMPT_PATH`.
+fn run_filter(command: &str, path: &Path, content: &str) -> anyhow::Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let mut child = shell
+        .arg(command)
+        .env("CARGO_PROMPT_PATH", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Written from another thread so a command that prints before reading everything can't deadlock
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = content.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // A command that doesn't read all of its input closes the pipe early, which is fine
+    let _ = writer.join();
+
+    if !output.status.success() {
+        anyhow::bail!("{}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Runs `cargo expand` for the package in `dir` and returns the expanded source.
+fn cargo_expand(dir: &Path) -> anyhow::Result<String> {
+    // Set when running as `cargo prompt`
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .arg("expand")
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow::anyhow!("couldn't run cargo: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no such command") {
+            anyhow::bail!("--expand-macros needs cargo-expand; install it with `cargo install cargo-expand`");
+        }
+        anyhow::bail!("cargo expand failed: {}", stderr.trim());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Runs a git command in `dir` and returns its trimmed stdout, failing with git's own error message.
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Returns the canonical paths of files changed between the merge-base of `base` and HEAD,
+/// including uncommitted modifications in the working tree.
+fn git_branch_changes(dir: &Path, base: &str) -> anyhow::Result<HashSet<PathBuf>> {
+    let merge_base = git(dir, &["merge-base", base, "HEAD"])?;
+    git_changed_since(dir, &merge_base)
+}
+
+/// Reads the `--files-from` list: one path per line, from stdin for `-`. Blank lines are skipped.
+fn read_file_list(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let list = if path == Path::new("-") {
+        let mut list = String::new();
+        std::io::stdin().read_to_string(&mut list)?;
+        list
+    } else {
+        fs::read_to_string(path).map_err(|e| anyhow::anyhow!("couldn't read file list {}: {}", path.display(), e))?
+    };
+    Ok(list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Whether `path` was modified at most `within` ago. Modification times in the future count
+/// as recent.
+fn modified_within(path: &Path, within: std::time::Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified.elapsed().map_or(true, |age| age <= within))
+}
+
+/// Returns the canonical paths of files that differ between `revision` and the working tree.
+fn git_changed_since(dir: &Path, revision: &str) -> anyhow::Result<HashSet<PathBuf>> {
+    let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?);
+    let changed = git(dir, &["diff", "--name-only", revision])?;
+
+    Ok(changed
+        .lines()
+        .filter_map(|line| fs::canonicalize(root.join(line)).ok())
+        .collect())
+}
+
 /// Remove line and block comments from the string, preserving everything else (including whitespace).
 ///
-/// - `line_comment` is something like "#" or "//"
-/// - `block_comment_start` is something like "/*" or "'''"
-/// - `block_comment_end` is something like "*/" or "'''"
+/// - `line_comments` are tokens like "#" or "//"; any of them starts a line comment
+/// - `block_comments` are delimiter pairs like ("/*", "*/") or ("'''", "'''")
+/// - `nested` allows block comments to contain other block comments, as in Rust and Haskell
+/// - `raw_strings` are copied as they are, like other string literals
+///
+/// An empty delimiter means the language has no comments of that kind.
 fn remove_documentation(
     content: &str,
-    line_comment: &str,
-    block_comment_start: &str,
-    block_comment_end: &str,
+    line_comments: &[&str],
+    block_comments: &[(&str, &str)],
+    nested: bool,
+    raw_strings: RawStrings,
 ) -> String {
-    let mut result = String::new();
+    let mask = comment_mask(content, line_comments, block_comments, nested, raw_strings);
+    content.chars().zip(mask).filter(|&(_, comment)| !comment).map(|(c, _)| c).collect()
+}
+
+/// Marks each character of `content` that belongs to a comment, per the same rules as
+/// [`remove_documentation`]. The newline ending a line comment isn't part of it.
+fn comment_mask(
+    content: &str,
+    line_comments: &[&str],
+    block_comments: &[(&str, &str)],
+    nested: bool,
+    raw_strings: RawStrings,
+) -> Vec<bool> {
+    char_kinds(content, line_comments, block_comments, nested, raw_strings)
+        .into_iter()
+        .map(|kind| kind == CharKind::Comment)
+        .collect()
+}
+
+/// What a character of source code is part of.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharKind {
+    Code,
+    Comment,
+    /// A double-quoted or raw string literal, quotes included
+    Literal,
+}
+
+/// Classifies each character of `content`, with the arguments of [`remove_documentation`].
+fn char_kinds(
+    content: &str,
+    line_comments: &[&str],
+    block_comments: &[(&str, &str)],
+    nested: bool,
+    raw_strings: RawStrings,
+) -> Vec<CharKind> {
+    let mut mask = Vec::new();
 
     let mut in_string = false;
     let mut in_char = false;
     let mut in_line_comment = false;
-    let mut in_block_comment = false;
+    // The kind of block comment we're inside, and how deep (only ever 1 unless `nested`)
+    let mut block: Option<(&str, &str)> = None;
+    let mut block_depth = 0usize;
 
     let mut prev_char = None;
-    let mut chars = content.chars().peekable();
+    let chars: Vec<char> = content.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
 
-    while let Some(c) = chars.next() {
         // If we're in a line comment, consume until newline
         if in_line_comment {
-            if c == '\n' {
-                in_line_comment = false;
-                // Keep the newline
-                result.push(c);
-            }
+            // Keep the newline
+            in_line_comment = c != '\n';
+            mask.push(if in_line_comment { CharKind::Comment } else { CharKind::Code });
             prev_char = Some(c);
+            i += 1;
             continue;
         }
 
-        // If we're in a block comment, look for the block_comment_end pattern
-        if in_block_comment {
-            // Check if we've hit the end of a block comment
-            if c == block_comment_end.chars().next().unwrap() {
-                let mut is_block_end = true;
-                for expected in block_comment_end.chars().skip(1) {
-                    if chars.next() != Some(expected) {
-                        is_block_end = false;
-                        break;
-                    }
-                }
-                if is_block_end {
-                    in_block_comment = false;
+        // If we're in a block comment, look for its end delimiter
+        if let Some((start, end)) = block {
+            let skipped = if starts_with_at(&chars, i, end) {
+                block_depth -= 1;
+                if block_depth == 0 {
+                    block = None;
                 }
-            }
+                end.chars().count()
+            } else if nested && starts_with_at(&chars, i, start) {
+                block_depth += 1;
+                start.chars().count()
+            } else {
+                1
+            };
+            mask.extend(std::iter::repeat_n(CharKind::Comment, skipped));
+            i += skipped;
             prev_char = Some(c);
             continue;
         }
 
-        // Handle string toggling
+        // If not in a string or char, check if this is the start of a comment. Block comments
+        // are checked first since their opener often begins with the line comment token (`--[[`).
+        if !in_string && !in_char {
+            if let Some(end) = raw_strings.literal_end(&chars, i) {
+                mask.extend(std::iter::repeat_n(CharKind::Literal, end - i));
+                prev_char = chars.get(end - 1).copied();
+                i = end;
+                continue;
+            }
+            if let Some(&(start, end)) = block_comments.iter().find(|(start, _)| starts_with_at(&chars, i, start)) {
+                block = Some((start, end));
+                block_depth = 1;
+                mask.extend(std::iter::repeat_n(CharKind::Comment, start.chars().count()));
+                i += start.chars().count();
+                prev_char = Some(c);
+                continue;
+            }
+            if let Some(token) = line_comments.iter().find(|token| starts_with_at(&chars, i, token)) {
+                in_line_comment = true;
+                mask.extend(std::iter::repeat_n(CharKind::Comment, token.chars().count()));
+                i += token.chars().count();
+                prev_char = Some(c);
+                continue;
+            }
+        }
+
+        // Toggle string / char literal if not escaped
+        let was_in_string = in_string;
         match c {
-            '"' if !in_char => {
-                // Toggle string if not escaped
-                if prev_char != Some('\\') {
-                    in_string = !in_string;
+            '"' if !in_char && prev_char != Some('\\') => in_string = !in_string,
+            '\'' if !in_string && prev_char != Some('\\') => in_char = !in_char,
+            _ => {}
+        }
+
+        mask.push(if was_in_string || in_string { CharKind::Literal } else { CharKind::Code });
+        prev_char = Some(c);
+        i += 1;
+    }
+
+    mask
+}
+
+/// Kinds of raw string literal, whose contents the comment and whitespace scanners must copy
+/// as they are: a `\` in them doesn't escape the way it does in ordinary strings.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RawStrings {
+    None,
+    /// `r"..."`, `r#"..."#`, `br##"..."##`, ...
+    Rust,
+    /// `r"..."`, `rb'...'`, `r"""..."""`, ..., where a `\` still keeps a quote from ending the string
+    Python,
+    /// C#'s `@"..."` and `$@"..."`, where `""` stands for a quote
+    Verbatim,
+    /// Go's `` `...` ``, which has no escapes at all
+    Backtick,
+}
+
+impl RawStrings {
+    fn of(language: &str) -> Self {
+        match language {
+            "rust" => RawStrings::Rust,
+            "python" => RawStrings::Python,
+            "csharp" => RawStrings::Verbatim,
+            "go" => RawStrings::Backtick,
+            _ => RawStrings::None,
+        }
+    }
+
+    /// End (exclusive) of the raw string literal starting at `i`, if one does. Unterminated
+    /// literals run to the end of the input.
+    fn literal_end(self, chars: &[char], i: usize) -> Option<usize> {
+        // The prefix must start a token: `bar"` is not `r"`
+        if i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_') {
+            return None;
+        }
+        let at = |j: usize| chars.get(j).copied();
+        match self {
+            RawStrings::None => None,
+            RawStrings::Rust => {
+                let r = if matches!(at(i), Some('b' | 'c')) { i + 1 } else { i };
+                if at(r) != Some('r') {
+                    return None;
                 }
-                result.push(c);
-            }
-            '\'' if !in_string => {
-                // Toggle char literal if not escaped
-                if prev_char != Some('\\') {
-                    in_char = !in_char;
+                let hashes = chars[r + 1..].iter().take_while(|&&c| c == '#').count();
+                let open = r + 1 + hashes;
+                if at(open) != Some('"') {
+                    return None;
                 }
-                result.push(c);
+                let closing: Vec<char> = std::iter::once('"').chain(std::iter::repeat_n('#', hashes)).collect();
+                let end = (open + 1..chars.len()).find(|&j| chars[j..].starts_with(&closing));
+                Some(end.map_or(chars.len(), |end| end + closing.len()))
             }
-            _ => {
-                // If not in a string or char, check if this is the start of a comment
-                if !in_string && !in_char {
-                    // Check for line comment
-                    if c == line_comment.chars().next().unwrap() {
-                        let mut is_line = true;
-                        for expected in line_comment.chars().skip(1) {
-                            if chars.next() != Some(expected) {
-                                is_line = false;
-                                break;
-                            }
-                        }
-                        if is_line {
-                            in_line_comment = true;
-                            prev_char = Some(c);
-                            continue;
-                        } else {
-                            // Not actually a comment, so push the character we saw + any consumed
-                            result.push(c);
-                            prev_char = Some(c);
-                            continue;
-                        }
+            RawStrings::Python => {
+                let prefix: String = chars[i..].iter().take_while(|c| c.is_ascii_alphabetic()).collect();
+                if !matches!(prefix.to_lowercase().as_str(), "r" | "rb" | "br" | "rf" | "fr") {
+                    return None;
+                }
+                let open = i + prefix.len();
+                let quote = at(open).filter(|&c| c == '"' || c == '\'')?;
+                let triple = at(open + 1) == Some(quote) && at(open + 2) == Some(quote);
+                let delimiter = vec![quote; if triple { 3 } else { 1 }];
+                let mut j = open + delimiter.len();
+                while j < chars.len() {
+                    match chars[j] {
+                        '\\' => j += 2,
+                        _ if chars[j..].starts_with(&delimiter) => return Some(j + delimiter.len()),
+                        '\n' if !triple => return Some(j),
+                        _ => j += 1,
                     }
-
-                    // Check for block comment
-                    if c == block_comment_start.chars().next().unwrap() {
-                        let mut is_block = true;
-                        for expected in block_comment_start.chars().skip(1) {
-                            if chars.next() != Some(expected) {
-                                is_block = false;
-                                break;
-                            }
-                        }
-                        if is_block {
-                            in_block_comment = true;
-                            prev_char = Some(c);
-                            continue;
-                        } else {
-                            // Not actually a block comment, push char + any consumed
-                            result.push(c);
-                            prev_char = Some(c);
-                            continue;
-                        }
+                }
+                Some(chars.len())
+            }
+            RawStrings::Verbatim => {
+                let open = match (at(i), at(i + 1), at(i + 2)) {
+                    (Some('@'), Some('"'), _) => i + 1,
+                    (Some('@'), Some('$'), Some('"')) | (Some('$'), Some('@'), Some('"')) => i + 2,
+                    _ => return None,
+                };
+                let mut j = open + 1;
+                while j < chars.len() {
+                    match (chars[j], at(j + 1)) {
+                        ('"', Some('"')) => j += 2,
+                        ('"', _) => return Some(j + 1),
+                        _ => j += 1,
                     }
                 }
+                Some(chars.len())
+            }
+            RawStrings::Backtick => {
+                if at(i) != Some('`') {
+                    return None;
+                }
+                let end = (i + 1..chars.len()).find(|&j| chars[j] == '`');
+                Some(end.map_or(chars.len(), |end| end + 1))
+            }
+        }
+    }
+}
 
-                // Otherwise, just push the character
-                result.push(c);
+/// Blanks out whole-line comments marked by one of `markers` in the 1-based `column`, as in
+/// fixed-form Fortran (`C` in column 1) and COBOL (`*` in column 7). Line breaks are kept.
+fn remove_column_comments(content: &str, column: usize, markers: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            if line.chars().nth(column - 1).is_some_and(|c| markers.contains(c)) {
+                if line.ends_with('\n') { "\n" } else { "" }
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+/// Blanks batch file comment lines. `REM` and `::` are only comments as a command of their
+/// own, so `echo REM` or `a::b` later in a line are left alone.
+fn remove_batch_comments(content: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let command = line.trim_start().trim_start_matches('@');
+            let first_word = command.split_whitespace().next().unwrap_or("");
+            if command.starts_with("::") || first_word.eq_ignore_ascii_case("rem") {
+                if line.ends_with('\n') { "\n" } else { "" }
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+/// Splits off the comment block(s) at the very top of a file when they look like a license
+/// header (mentioning a copyright, license, or SPDX identifier). Returns `(header, rest)`, with
+/// an empty header when there is none.
+fn split_license_header<'a>(
+    content: &'a str,
+    line_comments: &[&str],
+    block_comments: &[(&str, &str)],
+) -> (&'a str, &'a str) {
+    let mut end = 0;
+
+    loop {
+        let remaining = &content[end..];
+        let trimmed = remaining.trim_start();
+        let start = end + remaining.len() - trimmed.len();
+
+        let block = block_comments
+            .iter()
+            .find(|(block_start, _)| !block_start.is_empty() && trimmed.starts_with(block_start));
+        let comment_end = if let Some((block_start, block_end)) = block {
+            let after_start = start + block_start.len();
+            match content[after_start..].find(block_end) {
+                Some(offset) => after_start + offset + block_end.len(),
+                None => break,
             }
+        } else if line_comments.iter().any(|token| !token.is_empty() && trimmed.starts_with(token)) {
+            content[start..].find('\n').map_or(content.len(), |offset| start + offset)
+        } else {
+            break;
+        };
+
+        // Take the rest of the line too, unless there's code after the comment
+        let line_rest = content[comment_end..].find('\n').map_or(content.len(), |offset| comment_end + offset + 1);
+        if !content[comment_end..line_rest].trim().is_empty() {
+            break;
         }
+        end = line_rest;
+    }
 
-        prev_char = Some(c);
+    let header = &content[..end];
+    let lowercase = header.to_lowercase();
+    if ["copyright", "license", "licence", "spdx-license-identifier"]
+        .iter()
+        .any(|keyword| lowercase.contains(keyword))
+    {
+        (header, &content[end..])
+    } else {
+        ("", content)
     }
+}
+
+/// True when `token` is non-empty and appears in `chars` starting at index `i`.
+fn starts_with_at(chars: &[char], i: usize, token: &str) -> bool {
+    !token.is_empty()
+        && token
+            .chars()
+            .enumerate()
+            .all(|(offset, expected)| chars.get(i + offset) == Some(&expected))
+}
+
+/// Replace runs of two or more blank lines with a single blank line, leaving blank lines
+/// inside multi-line string literals and block comments alone. `handler` supplies the
+/// language's string and comment syntax; without one, every blank line counts.
+fn collapse_blank_lines(content: &str, handler: Option<&Handler>) -> String {
+    let kinds = handler.and_then(|handler| handler_char_kinds(content, handler));
+    let mut result = String::new();
+    let mut previous_blank = false;
+    // Index of the current line's first character in `kinds`
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let length = line.chars().count();
+        // A blank line is all whitespace, so any character of it tells whether it's code
+        let in_code = kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.get(offset + length - 1).is_none_or(|&kind| kind == CharKind::Code));
+        let blank = line.trim().is_empty() && in_code;
+        if !(blank && previous_blank) {
+            result.push_str(line);
+        }
+        previous_blank = blank;
+        offset += length;
+    }
+
+    result
+}
+
+/// Breaks lines longer than `width` columns for `--wrap`. Continuations keep the line's
+/// indentation and start with `marker`.
+fn wrap_lines(content: &str, width: usize, marker: &str) -> String {
+    let mut result = String::new();
+    for line in content.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let indent = &text[..text.len() - text.trim_start().len()];
+        let mut rest = text;
+        let mut prefix = String::new();
+        while let Some(at) = break_point(rest, width.saturating_sub(prefix.chars().count())) {
+            result.push_str(&prefix);
+            result.push_str(rest[..at].trim_end());
+            result.push('\n');
+            rest = rest[at..].trim_start();
+            prefix = format!("{}{}", indent, marker);
+        }
+        result.push_str(&prefix);
+        result.push_str(rest);
+        result.push_str(newline);
+    }
+    result
+}
+
+/// Where to break `line` so the first part fits in `width` columns: the last whitespace
+/// within the limit, or the first one after it when there is none. Whitespace inside quotes
+/// and the indentation don't count. `None` when the line fits or can't be broken.
+fn break_point(line: &str, width: usize) -> Option<usize> {
+    if line.chars().count() <= width {
+        return None;
+    }
+    let indent_end = line.len() - line.trim_start().len();
+    let content_end = line.trim_end().len();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut best = None;
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        if i >= content_end {
+            break;
+        }
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if matches!(c, '"' | '\'' | '`') => quote = Some(c),
+            None if c.is_whitespace() && i > indent_end => {
+                if column > width && best.is_some() {
+                    break;
+                }
+                best = Some(i);
+                if column > width {
+                    break;
+                }
+            }
+            None => {}
+        }
+    }
+    best
+}
+
+/// The comment starting each `--wrap` continuation line: a block comment where the language
+/// has one, so the rest of the line stays code, otherwise a line comment.
+fn continuation_marker(fence: &str) -> String {
+    let (line_comments, block_comments) = match fence {
+        "rust" | "javascript" => (&["//"][..], &[("/*", "*/")][..]),
+        fence => match LANGUAGES.iter().chain(CONFIG_FORMATS).find(|spec| spec.fence == fence) {
+            Some(spec) => (spec.line_comments, spec.block_comments),
+            None => (&[][..], &[][..]),
+        },
+    };
+    // Python's ''' blocks are really strings
+    let block = block_comments
+        .iter()
+        .find(|(start, _)| !start.is_empty() && !start.starts_with(['"', '\'']));
+    match (block, line_comments.iter().find(|start| !start.is_empty())) {
+        (Some((start, end)), _) => format!("{} ↪ {} ", start, end),
+        (None, Some(start)) => format!("{} ↪ ", start),
+        (None, None) => "↪ ".to_string(),
+    }
+}
+
+/// Prefixes each line with its 1-based line number, right-aligned to a common width.
+fn number_lines(content: &str) -> String {
+    let width = content.lines().count().to_string().len();
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>width$} | {}\n", i + 1, line, width = width))
+        .collect()
+}
 
+/// Minify for line-oriented languages: drops blank lines and indentation, and squeezes
+/// runs of spaces and tabs outside string literals into a single space, keeping one
+/// statement per line.
+///
+/// With `keep_tab_indent`, lines indented with a tab keep a single tab, since that's what
+/// marks a Makefile recipe line.
+fn remove_whitespace_within_lines(content: &str, keep_tab_indent: bool) -> String {
+    let mut result = String::new();
+    // Double-quoted strings may span lines; single-quoted ones are assumed not to
+    let mut in_string = false;
+
+    for line in content.lines() {
+        let recipe = keep_tab_indent && !in_string && line.starts_with('\t');
+        // Trailing spaces are dropped below, unless the line ends inside a string
+        let line = if in_string { line } else { line.trim_start() };
+        if line.is_empty() && !in_string {
+            continue;
+        }
+        if recipe {
+            result.push('\t');
+        }
+
+        let mut in_char = false;
+        let mut prev_char = None;
+        let mut pending_space = false;
+        for c in line.chars() {
+            if !in_string && !in_char && (c == ' ' || c == '\t') {
+                pending_space = true;
+                continue;
+            }
+            if pending_space {
+                result.push(' ');
+                pending_space = false;
+            }
+            match c {
+                '"' if !in_char && prev_char != Some('\\') => in_string = !in_string,
+                '\'' if !in_string && prev_char != Some('\\') => in_char = !in_char,
+                _ => {}
+            }
+            result.push(c);
+            prev_char = if prev_char == Some('\\') && c == '\\' { None } else { Some(c) };
+        }
+        result.push('\n');
+    }
+
+    result.pop();
     result
 }
 
 /// Remove extra whitespace, newlines, and other “non-code” spacing outside of string/char literals.
-fn remove_whitespace(content: &str) -> String {
+///
+/// `raw_strings` are copied verbatim too, as are backtick template strings and (heuristically)
+/// regex literals when `js_literals` is set.
+fn remove_whitespace(content: &str, raw_strings: RawStrings, js_literals: bool) -> String {
     let mut result = String::new();
 
     let mut in_string = false;
     let mut in_char = false;
+    let mut in_template = false;
+    let mut in_regex = false;
+    // Inside a `[...]` class, where a `/` doesn't end the regex
+    let mut in_regex_class = false;
     let mut prev_char = None;
-    let mut chars = content.chars().peekable();
+    let chars: Vec<char> = content.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if !in_string
+            && !in_char
+            && !in_template
+            && !in_regex
+            && let Some(end) = raw_strings.literal_end(&chars, i)
+        {
+            result.extend(&chars[i..end]);
+            prev_char = chars.get(end - 1).copied();
+            i = end;
+            continue;
+        }
+        i += 1;
+
+        // Template strings and regexes keep every character, including whitespace
+        if in_template || in_regex {
+            result.push(c);
+            match c {
+                '\\' => {
+                    if let Some(&next) = chars.get(i) {
+                        result.push(next);
+                        i += 1;
+                    }
+                }
+                '`' if in_template => in_template = false,
+                '[' if in_regex => in_regex_class = true,
+                ']' if in_regex => in_regex_class = false,
+                '/' if in_regex && !in_regex_class => in_regex = false,
+                // A regex never spans lines; this was a division after all
+                '\n' if in_regex => in_regex = false,
+                _ => {}
+            }
+            prev_char = Some(c);
+            continue;
+        }
 
-    while let Some(c) = chars.next() {
         match c {
             // Toggle string if not escaped
             '"' => {
@@ -1041,17 +4660,35 @@ fn remove_whitespace(content: &str) -> String {
                 }
                 result.push(c);
             }
+            '`' if js_literals && !in_string && !in_char => {
+                in_template = true;
+                result.push(c);
+            }
+            // A `/` where an operand is expected starts a regex rather than a division
+            '/' if js_literals
+                && !in_string
+                && !in_char
+                && !matches!(chars.get(i), Some('/' | '*'))
+                && result
+                    .chars()
+                    .last()
+                    .is_none_or(|prev| "(,=:[!&|?{};+-*%<>~^".contains(prev)) =>
+            {
+                in_regex = true;
+                in_regex_class = false;
+                result.push(c);
+            }
             '\n' | '\r' | '\t' | ' ' => {
-                // If we're inside a string/char, keep whitespace (for correctness of literal).
-                // Otherwise, skip it.
+                // If we're inside a string/char, keep whitespace (for correctness of literal),
+                // including line breaks in multi-line strings. Otherwise, skip it, except for one
+                // space at the end of the run where dropping it would fuse two tokens (`int x`,
+                // `a - -b`).
                 if in_string || in_char {
-                    if c == '\n' || c == '\r' {
-                        // Convert newlines inside string to \n (optional).
-                        result.push_str("\\n");
-                    } else {
-                        // Keep the space or tab inside the literal
-                        result.push(c);
-                    }
+                    result.push(c);
+                } else if let (Some(prev), Some(&next)) = (result.chars().last(), chars.get(i))
+                    && ((typescript::is_word(prev) && typescript::is_word(next)) || (prev == next && "+-".contains(next)))
+                {
+                    result.push(' ');
                 }
             }
             '\\' => {
@@ -1060,9 +4697,10 @@ fn remove_whitespace(content: &str) -> String {
                     // Push backslash
                     result.push(c);
                     // If next char is an escapable character, push it too
-                    if let Some(&next) = chars.peek() {
+                    if let Some(&next) = chars.get(i) {
                         if matches!(next, 'n' | 'r' | 't' | '\\' | '"' | '\'') {
-                            result.push(chars.next().unwrap());
+                            result.push(next);
+                            i += 1;
                         }
                     }
                 } else {
@@ -1086,3 +4724,255 @@ fn remove_whitespace(content: &str) -> String {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collapse_rust(content: &str) -> String {
+        collapse_blank_lines(content, Some(&Handler::Rust))
+    }
+
+    #[test]
+    fn collapses_runs_of_blank_lines() {
+        assert_eq!(collapse_rust("a();\n\n\n\nb();\n\n\nc();\n"), "a();\n\nb();\n\nc();\n");
+        assert_eq!(collapse_blank_lines("x\n \n\t\n\ny\n", None), "x\n \ny\n");
+    }
+
+    #[test]
+    fn keeps_blank_lines_inside_string_literals() {
+        let code = "let s = \"one\n\n\n\ntwo\";\nlet r = r#\"raw \" quote\n\n\n\"#;\n\n\n\nend();\n";
+        assert_eq!(
+            collapse_rust(code),
+            "let s = \"one\n\n\n\ntwo\";\nlet r = r#\"raw \" quote\n\n\n\"#;\n\nend();\n"
+        );
+    }
+
+    #[test]
+    fn quotes_in_chars_and_comments_do_not_start_strings() {
+        let code = "let q = '\"';\n\n\n\n// say \"hi\n\n\n\nf();\n";
+        assert_eq!(collapse_rust(code), "let q = '\"';\n\n// say \"hi\n\nf();\n");
+    }
+
+    #[test]
+    fn removes_nested_rust_block_comments() {
+        let code = "a(); /* outer /* inner */ still comment */ b();";
+        assert_eq!(remove_documentation(code, &["//"], &[("/*", "*/")], true, RawStrings::Rust), "a();  b();");
+    }
+
+    #[test]
+    fn removes_nested_haskell_block_comments() {
+        let code = "x = 1 {- outer {- inner {- deepest -} -} still comment -}\ny = 2 -- trailing\n";
+        assert_eq!(remove_documentation(code, &["--"], &[("{-", "-}")], true, RawStrings::None), "x = 1 \ny = 2 \n");
+    }
+
+    #[test]
+    fn unnested_block_comments_end_at_the_first_delimiter() {
+        let code = "a(); /* outer /* inner */ b();";
+        assert_eq!(remove_documentation(code, &["//"], &[("/*", "*/")], false, RawStrings::None), "a();  b();");
+    }
+
+    #[test]
+    fn splits_off_a_license_header() {
+        let code = "// Copyright 2024 Example\n// SPDX-License-Identifier: MIT\n\n/* more */\nfn main() {} // inline\n";
+        let (header, rest) = split_license_header(code, &["//"], &[("/*", "*/")]);
+        assert_eq!(header, "// Copyright 2024 Example\n// SPDX-License-Identifier: MIT\n\n/* more */\n");
+        assert_eq!(rest, "fn main() {} // inline\n");
+    }
+
+    #[test]
+    fn other_leading_comments_are_not_a_license() {
+        let code = "// Helpers for parsing\nfn main() {}\n";
+        assert_eq!(split_license_header(code, &["//"], &[("/*", "*/")]), ("", code));
+    }
+
+    #[test]
+    fn notebooks_become_python_scripts() {
+        let notebook = r##"{"cells": [
+            {"cell_type": "markdown", "source": ["# Title\n", "Some text"]},
+            {"cell_type": "code", "source": ["import os\n", "x = 1"]},
+            {"cell_type": "raw", "source": "ignored"},
+            {"cell_type": "code", "source": "print(x)\n"}
+        ]}"##;
+        assert_eq!(notebook_to_python(notebook).unwrap(), "# # Title\n# Some text\n\nimport os\nx = 1\n\nprint(x)\n\n");
+        assert!(notebook_to_python("{}").is_err());
+    }
+
+    #[test]
+    fn display_paths_are_relative_with_forward_slashes() {
+        let root = Path::new("../../project");
+        assert_eq!(display_path(Path::new("../../project/src/main.rs"), root, None), "src/main.rs");
+        assert_eq!(display_path(Path::new("./src/main.rs"), Path::new("."), None), "src/main.rs");
+        assert_eq!(display_path(Path::new("src\\win\\mod.rs"), Path::new("."), None), "src/win/mod.rs");
+        assert_eq!(display_path(Path::new("../../project/src/main.rs"), root, Some("project/")), "project/src/main.rs");
+    }
+
+    #[test]
+    fn reads_the_shebang_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, contents).unwrap();
+            shebang_interpreter(&path)
+        };
+        assert_eq!(script("a", "#!/usr/bin/env python3\nprint(1)\n").as_deref(), Some("python"));
+        assert_eq!(script("b", "#!/usr/bin/env -S node --harmony\n").as_deref(), Some("node"));
+        assert_eq!(script("c", "#!/bin/bash -e\n").as_deref(), Some("bash"));
+        assert_eq!(script("d", "#!/usr/bin/python3.12\n").as_deref(), Some("python"));
+        assert_eq!(script("e", "plain text\n"), None);
+    }
+
+    #[test]
+    fn batch_comments_are_whole_commands() {
+        let code = "@echo off\nREM a remark\n:: another\n  @rem indented\nREM\necho REM not a comment\nset a=b::c\n";
+        assert_eq!(remove_batch_comments(code), "@echo off\n\n\n\n\necho REM not a comment\nset a=b::c\n");
+    }
+
+    #[test]
+    fn strips_every_line_comment_token() {
+        let code = "SELECT 1; -- dash\n# hash\nSELECT '-- # kept';\n";
+        assert_eq!(
+            remove_documentation(code, &["--", "#"], &[("/*", "*/")], false, RawStrings::None),
+            "SELECT 1; \n\nSELECT '-- # kept';\n"
+        );
+    }
+
+    #[test]
+    fn human_sizes_use_binary_units() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(4300), "4.2 KiB");
+        assert_eq!(human_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn line_oriented_minify_keeps_line_breaks() {
+        let script = "#!/bin/sh\nset -e\n\n   cd /tmp &&   ls\necho   'a  b'   \"c  \n  d\"\nexit 0\n";
+        assert_eq!(
+            remove_whitespace_within_lines(script, false),
+            "#!/bin/sh\nset -e\ncd /tmp && ls\necho 'a  b' \"c  \n  d\"\nexit 0"
+        );
+    }
+
+    #[test]
+    fn template_literals_keep_their_spaces() {
+        let code = "let s = `a   b\n   ${ x }  c`;\nlet t = 1;";
+        assert_eq!(remove_whitespace(code, RawStrings::None, true), "let s=`a   b\n   ${ x }  c`;let t=1;");
+    }
+
+    #[test]
+    fn regex_literals_keep_their_spaces() {
+        let code = "if (x) { m = s.match( /a b\\/ [ /]c/g ); }\nlet half = a / 2;";
+        assert_eq!(remove_whitespace(code, RawStrings::None, true), "if(x){m=s.match(/a b\\/ [ /]c/g);}let half=a/2;");
+    }
+
+    #[test]
+    fn column_comments_are_whole_lines_marked_in_their_column() {
+        let cobol = "000100 IDENTIFICATION DIVISION.\n000200*COMMENT\n000300/PAGE BREAK\n000400     DISPLAY \"A*B\".";
+        assert_eq!(
+            remove_column_comments(cobol, 7, "*/"),
+            "000100 IDENTIFICATION DIVISION.\n\n\n000400     DISPLAY \"A*B\"."
+        );
+        let fortran = "C     COMMENT\n      X = 1\n*     ANOTHER\n      Y = X * 2\n";
+        assert_eq!(remove_column_comments(fortran, 1, "Cc*!"), "\n      X = 1\n\n      Y = X * 2\n");
+    }
+
+    #[test]
+    fn anchors_follow_github_slugs() {
+        let mut anchors = Anchors::default();
+        assert_eq!(anchors.anchor(" src/Main.rs"), "srcmainrs");
+        assert_eq!(anchors.anchor("My Script.py (2)"), "my-scriptpy-2");
+        assert_eq!(anchors.anchor("a-b.py"), "a-bpy");
+        // Headings with the same slug are numbered
+        assert_eq!(anchors.anchor("a b.py"), "a-bpy-1");
+        assert_eq!(anchors.anchor("a-b.py"), "a-bpy-2");
+    }
+
+    #[test]
+    fn normalizes_the_bom_and_line_endings() {
+        assert_eq!(normalize_text("\u{feff}a\r\nb\rc\n".to_string()), "a\nb\nc\n");
+        // Only a leading mark is a BOM
+        assert_eq!(normalize_text("a\u{feff}\n".to_string()), "a\u{feff}\n");
+    }
+
+    #[test]
+    fn wraps_at_the_last_space_that_fits() {
+        let wrapped = wrap_lines("    let total = first + second + third;\nshort\n", 24, "/* ↪ */ ");
+        assert_eq!(wrapped, "    let total = first +\n    /* ↪ */ second +\n    /* ↪ */ third;\nshort\n");
+    }
+
+    #[test]
+    fn never_wraps_inside_strings() {
+        let line = "print(\"a b c d e f g\") + x";
+        assert_eq!(break_point(line, 10), Some(22));
+        assert_eq!(break_point("\"a b c d e f g\"", 5), None);
+    }
+
+    #[test]
+    fn parses_extension_mappings() {
+        assert_eq!(parse_extension_mapping(".inc=php"), Ok(("inc".to_string(), "php")));
+        assert!(parse_extension_mapping("inc").unwrap_err().contains("EXT=LANGUAGE"));
+        assert!(parse_extension_mapping("inc=klingon").unwrap_err().contains("unknown language `klingon`"));
+    }
+
+    #[test]
+    fn newlines_inside_strings_are_kept() {
+        let code = "s = \"\"\"first\n    second\n\"\"\"\nt = 'a\\nb'\n";
+        assert_eq!(remove_whitespace(code, RawStrings::Python, false), "s=\"\"\"first\n    second\n\"\"\"t='a\\nb'");
+        let code = "let s = \"one\n  two\";\nlet t = 1;";
+        assert_eq!(remove_whitespace(code, RawStrings::Rust, false), "let s=\"one\n  two\";let t=1;");
+    }
+
+    #[test]
+    fn raw_strings_are_copied_verbatim() {
+        let rust = "let s = r#\"// not a comment \"quoted\"\"#; // gone\nlet t = r\"\\\"; /* gone */";
+        assert_eq!(
+            remove_documentation(rust, &["//"], &[("/*", "*/")], true, RawStrings::Rust),
+            "let s = r#\"// not a comment \"quoted\"\"#; \nlet t = r\"\\\"; "
+        );
+        assert_eq!(
+            remove_whitespace("let s = r#\"a  \" b\"#;\nlet t = 1;", RawStrings::Rust, false),
+            "let s=r#\"a  \" b\"#;let t=1;"
+        );
+
+        let python = "p = r'\\'#x' # gone\n";
+        assert_eq!(remove_documentation(python, &["#"], &[], false, RawStrings::Python), "p = r'\\'#x' \n");
+
+        let csharp = "p = @\"C:\\dir\\\"; // gone\nq = @\"say \"\"// hi\"\"\";";
+        assert_eq!(
+            remove_documentation(csharp, &["//"], &[("/*", "*/")], false, RawStrings::Verbatim),
+            "p = @\"C:\\dir\\\"; \nq = @\"say \"\"// hi\"\"\";"
+        );
+    }
+
+    #[test]
+    fn makefile_recipes_keep_one_leading_tab() {
+        let makefile = "all:   build\n\nbuild:\n\t\tcargo   build\n    echo not a recipe\n";
+        assert_eq!(remove_whitespace_within_lines(makefile, true), "all: build\nbuild:\n\tcargo build\necho not a recipe");
+        assert_eq!(remove_whitespace_within_lines(makefile, false), "all: build\nbuild:\ncargo build\necho not a recipe");
+    }
+
+    #[test]
+    fn words_on_separate_lines_stay_apart() {
+        let code = "int\nmain()\n{\n    return\n        x\n    ;\n    a = b\n    - -c;\n}";
+        assert_eq!(remove_whitespace(code, RawStrings::None, false), "int main(){return x;a=b- -c;}");
+        assert_eq!(remove_whitespace("x = a\n+ +b\ny = 1", RawStrings::None, false), "x=a+ +b y=1");
+    }
+
+    #[test]
+    fn models_are_looked_up_by_name() {
+        assert_eq!(parse_model(" GPT-4 "), Ok(("gpt-4", 8_192)));
+        let error = parse_model("gpt-5000").unwrap_err();
+        assert!(error.starts_with("unknown model `gpt-5000`; known models are gpt-4o, "), "{}", error);
+    }
+
+    #[test]
+    fn backticks_and_slashes_only_guard_javascript_like_languages() {
+        // A Haskell section and a stray backtick neither start a regex nor a template
+        let code = "half = map (/   2)   xs\nnote = 1 ` b\nc   =   2";
+        assert_eq!(remove_whitespace(code, RawStrings::None, false), "half=map(/2)xs note=1`b c=2");
+        // ...but Go's raw strings are still kept as they are
+        let go = "s := `a   b`\nt := 1";
+        assert_eq!(remove_whitespace(go, RawStrings::Backtick, false), "s:=`a   b`t:=1");
+        assert_eq!(remove_documentation("u := `http://x`\n", &["//"], &[], false, RawStrings::Backtick), "u := `http://x`\n");
+    }
+}
@@ -0,0 +1,106 @@
+//! `--interactive`: a terminal file picker for choosing exactly which files go into the
+//! prompt, built on ratatui.
+//!
+//! Every candidate starts out checked. The footer keeps a running token estimate for the
+//! checked files, from their size before any minification.
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+/// A file offered in the picker.
+pub struct Candidate {
+    pub display_path: String,
+    pub tokens: usize,
+}
+
+/// Shows `candidates` with checkboxes until the user confirms or cancels. Returns which
+/// were checked, or `None` when cancelled.
+pub fn pick(candidates: &[Candidate]) -> anyhow::Result<Option<Vec<bool>>> {
+    let mut picker = Picker {
+        candidates,
+        checked: vec![true; candidates.len()],
+        state: ListState::default().with_selected((!candidates.is_empty()).then_some(0)),
+    };
+    let mut terminal = ratatui::init();
+    let result = picker.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct Picker<'a> {
+    candidates: &'a [Candidate],
+    checked: Vec<bool>,
+    state: ListState,
+}
+
+impl Picker<'_> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<Option<Vec<bool>>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            // Windows reports key releases too
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => return Ok(Some(self.checked.clone())),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+                KeyCode::PageDown => self.state.scroll_down_by(10),
+                KeyCode::PageUp => self.state.scroll_up_by(10),
+                KeyCode::Home => self.state.select_first(),
+                KeyCode::End => self.state.select_last(),
+                KeyCode::Char(' ') => {
+                    if let Some(checked) = self.state.selected().and_then(|i| self.checked.get_mut(i)) {
+                        *checked = !*checked;
+                    }
+                }
+                // Check everything, or uncheck everything when it's all checked already
+                KeyCode::Char('a') => {
+                    let all = self.checked.iter().all(|&checked| checked);
+                    self.checked.fill(!all);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, footer_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+            .candidates
+            .iter()
+            .zip(&self.checked)
+            .map(|(candidate, &checked)| {
+                let mark = if checked { "[x]" } else { "[ ]" };
+                ListItem::new(format!("{} {}  ~{} tokens", mark, candidate.display_path, candidate.tokens))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" cargo prompt: pick files "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+
+        let (files, tokens) = self
+            .candidates
+            .iter()
+            .zip(&self.checked)
+            .filter(|&(_, &checked)| checked)
+            .fold((0, 0), |(files, tokens), (candidate, _)| (files + 1, tokens + candidate.tokens));
+        let footer = format!(
+            " {}/{} file(s), ~{} tokens before minification | space: toggle  a: all  enter: write prompt  q: cancel",
+            files,
+            self.candidates.len(),
+            tokens
+        );
+        frame.render_widget(Paragraph::new(Line::from(footer)), footer_area);
+    }
+}
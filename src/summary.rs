@@ -0,0 +1,106 @@
+//! Structural summaries used in place of the full body of very large files.
+//!
+//! A summary keeps imports, top-level declarations, and the first line of each
+//! function, which is usually enough for a model to understand how the file fits in.
+
+use rustminify::remove_docs;
+
+/// Summarizes Rust source via `syn`: every item is kept, but function bodies are cut
+/// down to their first statement. Falls back to the line heuristic if parsing fails.
+pub fn summarize_rust(code: &str, strip_docs: bool) -> String {
+    let Ok(ast) = syn::parse_file(code) else {
+        return summarize_lines(code, &["//"]);
+    };
+    let mut ast = if strip_docs { remove_docs(ast) } else { ast };
+    ast.items.iter_mut().for_each(summarize_item);
+    prettyplease::unparse(&ast)
+}
+
+fn summarize_item(item: &mut syn::Item) {
+    match item {
+        syn::Item::Fn(item) => truncate_block(&mut item.block),
+        syn::Item::Impl(item) => {
+            for impl_item in &mut item.items {
+                if let syn::ImplItem::Method(method) = impl_item {
+                    truncate_block(&mut method.block);
+                }
+            }
+        }
+        syn::Item::Trait(item) => {
+            for trait_item in &mut item.items {
+                if let syn::TraitItem::Method(method) = trait_item
+                    && let Some(block) = &mut method.default
+                {
+                    truncate_block(block);
+                }
+            }
+        }
+        syn::Item::Mod(item) => {
+            if let Some((_, items)) = &mut item.content {
+                items.iter_mut().for_each(summarize_item);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn truncate_block(block: &mut syn::Block) {
+    block.stmts.truncate(1);
+}
+
+/// Summarizes source in any language by indentation: unindented lines (imports and
+/// top-level declarations) are kept along with the first line of each indented block.
+/// Everything else is replaced by a `...` marker at the block's indentation.
+pub fn summarize_lines(code: &str, line_comments: &[&str]) -> String {
+    let mut summary = String::new();
+    let mut keep_next_indented = false;
+    let mut elided = false;
+
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || line_comments.iter().any(|token| !token.is_empty() && trimmed.starts_with(token)) {
+            continue;
+        }
+
+        let indented = trimmed.len() != line.len();
+        if !indented || keep_next_indented {
+            summary.push_str(line);
+            summary.push('\n');
+            keep_next_indented = !indented;
+            elided = false;
+        } else if !elided {
+            let indent = &line[..line.len() - trimmed.len()];
+            summary.push_str(indent);
+            summary.push_str("...\n");
+            elided = true;
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_functions_keep_their_first_statement() {
+        let code = "use std::fmt;\n\nfn f() -> u32 {\n    let a = 1;\n    let b = 2;\n    a + b\n}\n\nimpl S {\n    fn g(&self) {\n        one();\n        two();\n    }\n}\n";
+        let summary = summarize_rust(code, false);
+        assert!(summary.contains("use std::fmt;"));
+        assert!(summary.contains("fn f() -> u32 {\n    let a = 1;\n}"), "{}", summary);
+        assert!(summary.contains("one();") && !summary.contains("two();"), "{}", summary);
+    }
+
+    #[test]
+    fn unparseable_rust_falls_back_to_lines() {
+        let summary = summarize_rust("// note\nfn f( {\n    body();\n    more();\n}\n", false);
+        assert_eq!(summary, "fn f( {\n    body();\n    ...\n}\n");
+    }
+
+    #[test]
+    fn lines_keep_declarations_and_the_first_line_of_each_block() {
+        let code = "import os\n# comment\n\ndef f():\n    a = 1\n    b = 2\n    c = 3\n\nclass C:\n    x = 1\n";
+        assert_eq!(summarize_lines(code, &["#"]), "import os\ndef f():\n    a = 1\n    ...\nclass C:\n    x = 1\n");
+    }
+}
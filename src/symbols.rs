@@ -0,0 +1,198 @@
+//! Top-level symbol extraction for the per-file table of contents.
+//!
+//! Rust is handled with `syn`, which is always available. Other languages need a
+//! parser, so they are only supported when built with the `tree-sitter` feature.
+
+use std::path::Path;
+
+/// Returns the top-level symbols of `code` as `kind name` strings, or `None` when
+/// the language isn't supported or the file can't be parsed.
+pub fn symbols(language: &str, path: &Path, code: &str) -> Option<Vec<String>> {
+    if language == "rust" {
+        return rust_symbols(code);
+    }
+    tree_sitter_symbols(language, path, code)
+}
+
+/// Lists the top-level items of a Rust file.
+fn rust_symbols(code: &str) -> Option<Vec<String>> {
+    let file = syn::parse_file(code).ok()?;
+    let symbols = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(item) => Some(format!("fn {}", item.sig.ident)),
+            syn::Item::Struct(item) => Some(format!("struct {}", item.ident)),
+            syn::Item::Enum(item) => Some(format!("enum {}", item.ident)),
+            syn::Item::Union(item) => Some(format!("union {}", item.ident)),
+            syn::Item::Trait(item) => Some(format!("trait {}", item.ident)),
+            syn::Item::Type(item) => Some(format!("type {}", item.ident)),
+            syn::Item::Const(item) => Some(format!("const {}", item.ident)),
+            syn::Item::Static(item) => Some(format!("static {}", item.ident)),
+            syn::Item::Mod(item) => Some(format!("mod {}", item.ident)),
+            syn::Item::Macro(item) => item.ident.as_ref().map(|ident| format!("macro {}", ident)),
+            _ => None,
+        })
+        .collect();
+    Some(symbols)
+}
+
+/// Lists the public functions, types, traits, and modules of a Rust file for `--outline`,
+/// including those of public inline modules, e.g. `fn parse` or `struct config::Options`.
+pub fn rust_outline(code: &str) -> Option<Vec<String>> {
+    let file = syn::parse_file(code).ok()?;
+    let mut outline = Vec::new();
+    outline_items(&file.items, "", &mut outline);
+    Some(outline)
+}
+
+fn outline_items(items: &[syn::Item], prefix: &str, outline: &mut Vec<String>) {
+    let public = |vis: &syn::Visibility| matches!(vis, syn::Visibility::Public(_));
+    for item in items {
+        let (kind, ident) = match item {
+            syn::Item::Fn(item) if public(&item.vis) => ("fn", &item.sig.ident),
+            syn::Item::Struct(item) if public(&item.vis) => ("struct", &item.ident),
+            syn::Item::Enum(item) if public(&item.vis) => ("enum", &item.ident),
+            syn::Item::Trait(item) if public(&item.vis) => ("trait", &item.ident),
+            syn::Item::Mod(item) if public(&item.vis) => ("mod", &item.ident),
+            _ => continue,
+        };
+        outline.push(format!("{} {}{}", kind, prefix, ident));
+        if let syn::Item::Mod(syn::ItemMod { content: Some((_, items)), .. }) = item {
+            outline_items(items, &format!("{}{}::", prefix, ident), outline);
+        }
+    }
+}
+
+#[cfg(not(feature = "tree-sitter"))]
+fn tree_sitter_symbols(_language: &str, _path: &Path, _code: &str) -> Option<Vec<String>> {
+    None
+}
+
+/// Lists top-level functions, classes, and types using the bundled tree-sitter grammars.
+#[cfg(feature = "tree-sitter")]
+fn tree_sitter_symbols(language: &str, path: &Path, code: &str) -> Option<Vec<String>> {
+    let grammar: tree_sitter::Language = match language {
+        "python" => tree_sitter_python::LANGUAGE.into(),
+        "javascript" => tree_sitter_javascript::LANGUAGE.into(),
+        "typescript" if path.extension().and_then(|s| s.to_str()) == Some("tsx") => {
+            tree_sitter_typescript::LANGUAGE_TSX.into()
+        }
+        "typescript" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+        "go" => tree_sitter_go::LANGUAGE.into(),
+        "java" => tree_sitter_java::LANGUAGE.into(),
+        "cpp" => tree_sitter_cpp::LANGUAGE.into(),
+        "ruby" => tree_sitter_ruby::LANGUAGE.into(),
+        _ => return None,
+    };
+
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&grammar).ok()?;
+    let tree = parser.parse(code, None)?;
+    let root = tree.root_node();
+
+    let mut symbols = Vec::new();
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        collect_node(node, code.as_bytes(), &mut symbols);
+    }
+    Some(symbols)
+}
+
+/// Pushes the symbol(s) declared by a single top-level syntax node.
+#[cfg(feature = "tree-sitter")]
+fn collect_node(node: tree_sitter::Node, source: &[u8], symbols: &mut Vec<String>) {
+    let text = |node: tree_sitter::Node| node.utf8_text(source).ok().map(str::to_owned);
+
+    let kind = match node.kind() {
+        // Wrappers around the real declaration
+        "decorated_definition" | "export_statement" | "template_declaration" => {
+            let inner = node
+                .child_by_field_name("definition")
+                .or_else(|| node.child_by_field_name("declaration"));
+            if let Some(inner) = inner {
+                collect_node(inner, source, symbols);
+            }
+            return;
+        }
+        // Go groups type specs under a single declaration
+        "type_declaration" => {
+            let mut cursor = node.walk();
+            for spec in node.named_children(&mut cursor) {
+                if let Some(name) = spec.child_by_field_name("name").and_then(text) {
+                    symbols.push(format!("type {}", name));
+                }
+            }
+            return;
+        }
+        // C and C++ bury the function name inside nested declarators
+        "function_definition" if node.child_by_field_name("name").is_none() => {
+            if let Some(name) = declarator_name(node).and_then(text) {
+                symbols.push(format!("fn {}", name));
+            }
+            return;
+        }
+        "function_definition" | "method" | "singleton_method" => "def",
+        "function_declaration" | "generator_function_declaration" => "function",
+        "method_declaration" => "func",
+        "class_definition" | "class_declaration" | "abstract_class_declaration"
+        | "class_specifier" | "class" => "class",
+        "interface_declaration" => "interface",
+        "enum_declaration" | "enum_specifier" => "enum",
+        "record_declaration" => "record",
+        "type_alias_declaration" => "type",
+        "struct_specifier" => "struct",
+        "namespace_definition" => "namespace",
+        "module" => "module",
+        _ => return,
+    };
+
+    if let Some(name) = node.child_by_field_name("name").and_then(text) {
+        symbols.push(format!("{} {}", kind, name));
+    }
+}
+
+/// Follows `declarator` fields down to the identifier naming a C/C++ function.
+#[cfg(feature = "tree-sitter")]
+fn declarator_name(node: tree_sitter::Node) -> Option<tree_sitter::Node> {
+    let mut current = node.child_by_field_name("declarator")?;
+    loop {
+        match current.kind() {
+            "identifier" | "field_identifier" | "qualified_identifier" | "operator_name"
+            | "destructor_name" => return Some(current),
+            _ => current = current.child_by_field_name("declarator")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_top_level_rust_items() {
+        let code = "use std::fmt;\nstruct Point;\nfn main() {}\nmod util { fn inner() {} }\n";
+        let symbols = symbols("rust", Path::new("lib.rs"), code).unwrap();
+        assert_eq!(symbols, ["struct Point", "fn main", "mod util"]);
+    }
+
+    #[test]
+    fn outlines_public_items_of_inline_modules() {
+        let code = "pub fn parse() {}\nfn private() {}\npub mod config { pub struct Options; struct Hidden; }\n";
+        assert_eq!(rust_outline(code).unwrap(), ["fn parse", "mod config", "struct config::Options"]);
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn lists_python_functions_and_classes() {
+        let code = "import os\n\ndef load(path):\n    def helper():\n        pass\n\n@cached\ndef save():\n    pass\n\nclass Store:\n    def get(self):\n        pass\n";
+        let symbols = symbols("python", Path::new("store.py"), code).unwrap();
+        assert_eq!(symbols, ["def load", "def save", "class Store"]);
+    }
+
+    #[cfg(not(feature = "tree-sitter"))]
+    #[test]
+    fn other_languages_need_tree_sitter() {
+        assert_eq!(symbols("python", Path::new("store.py"), "def load():\n    pass\n"), None);
+    }
+}
@@ -0,0 +1,64 @@
+//! `--template`: renders the prompt through a user-supplied
+//! [TinyTemplate](https://docs.rs/tinytemplate) template instead of a built-in format.
+//!
+//! Templates see these values:
+//!
+//! - `project`: the project name
+//! - `files`: one entry per file, with `path`, `language`, `content`, `tokens`, `lines`
+//!   (before processing), and `summarized`
+//! - `total_files` and `total_tokens`
+//! - `omitted`: paths left out by `--max-tokens`
+//!
+//! ```text
+//! # {project}
+//! {{ for file in files }}
+//! <file path="{file.path}">
+//! {file.content}
+//! </file>
+//! {{ endfor }}
+//! ```
+//!
+//! Values are inserted as they are, without HTML escaping.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::json;
+use tinytemplate::TinyTemplate;
+
+use crate::{Section, estimate_tokens};
+
+/// Renders the template in `path` for `sections`.
+pub fn render(path: &Path, project: &str, sections: &[Section], omitted: &[String]) -> anyhow::Result<String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("couldn't read template {}: {}", path.display(), e))?;
+    let mut template = TinyTemplate::new();
+    template.set_default_formatter(&tinytemplate::format_unescaped);
+    template
+        .add_template("prompt", &source)
+        .map_err(|e| anyhow::anyhow!("invalid template {}: {}", path.display(), e))?;
+
+    let files: Vec<serde_json::Value> = sections
+        .iter()
+        .map(|section| {
+            json!({
+                "path": section.display_path,
+                "language": section.fence,
+                "content": section.content,
+                "tokens": estimate_tokens(&section.content),
+                "lines": section.source_lines,
+                "summarized": section.summarized,
+            })
+        })
+        .collect();
+    let context = json!({
+        "project": project,
+        "total_files": files.len(),
+        "total_tokens": sections.iter().map(|section| estimate_tokens(&section.content)).sum::<usize>(),
+        "files": files,
+        "omitted": omitted,
+    });
+    template
+        .render("prompt", &context)
+        .map_err(|e| anyhow::anyhow!("couldn't render template {}: {}", path.display(), e))
+}
@@ -0,0 +1,84 @@
+//! `--no-tests` / `--only-tests`: tells test files from the rest by the naming conventions
+//! of each language, without looking inside them.
+//!
+//! A file is a test when it's under a `tests`, `test`, `__tests__`, or `spec` directory, or
+//! its name follows its language's convention:
+//!
+//! | Language               | Test files                                         |
+//! |------------------------|----------------------------------------------------|
+//! | Go                     | `*_test.go`                                        |
+//! | Python                 | `test_*.py`, `*_test.py`, `conftest.py`            |
+//! | JavaScript, TypeScript | `*.test.ts`, `*.spec.ts`, also js, jsx, tsx, mjs, cjs |
+//! | Java, Kotlin           | `*Test.java`, `*Tests.java`, `*IT.java`, also kt   |
+//! | C#, PHP, Swift         | `*Test.cs`, `*Tests.cs`, also php, swift           |
+//! | Ruby                   | `*_spec.rb`, `*_test.rb`, `test_*.rb`              |
+//! | Dart, Elixir           | `*_test.dart`, `*_test.exs`                        |
+//! | C, C++                 | `*_test.c`, `test_*.c`, also cc, cpp, cxx          |
+//!
+//! Rust unit tests live next to the code in `#[cfg(test)]` modules, so only Rust files
+//! under `tests/` count.
+
+use std::path::{Component, Path};
+
+/// Directories holding nothing but tests.
+const TEST_DIRS: &[&str] = &["tests", "test", "__tests__", "spec"];
+
+/// Whether `path`, relative to the directory being walked, looks like a test file.
+pub fn is_test(path: &Path) -> bool {
+    let in_test_dir = path.parent().is_some_and(|dir| {
+        dir.components()
+            .any(|component| matches!(component, Component::Normal(name) if TEST_DIRS.iter().any(|test| name == *test)))
+    });
+    in_test_dir || path.file_name().and_then(|name| name.to_str()).is_some_and(is_test_name)
+}
+
+fn is_test_name(name: &str) -> bool {
+    let Some((stem, extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    match extension {
+        "go" | "dart" | "exs" => stem.ends_with("_test"),
+        "py" => stem.starts_with("test_") || stem.ends_with("_test") || stem == "conftest",
+        "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" => stem.ends_with(".test") || stem.ends_with(".spec"),
+        "java" | "kt" => ["Test", "Tests", "IT"].iter().any(|suffix| stem.ends_with(suffix)),
+        "cs" | "php" | "swift" => stem.ends_with("Test") || stem.ends_with("Tests"),
+        "rb" => stem.ends_with("_spec") || stem.ends_with("_test") || stem.starts_with("test_"),
+        "c" | "cc" | "cpp" | "cxx" => stem.ends_with("_test") || stem.starts_with("test_"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_follow_each_language() {
+        let tests = [
+            "server_test.go",
+            "test_api.py",
+            "api_test.py",
+            "conftest.py",
+            "button.spec.ts",
+            "util.test.js",
+            "UserTest.java",
+            "user_spec.rb",
+        ];
+        for test in tests {
+            assert!(is_test(Path::new(test)), "{}", test);
+        }
+        for code in ["server.go", "testing.py", "contest.py", "spec.ts", "button.ts", "Tester.java", "latest.go"] {
+            assert!(!is_test(Path::new(code)), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_directories_hold_only_tests() {
+        assert!(is_test(Path::new("tests/cli.rs")));
+        assert!(is_test(Path::new("src/__tests__/app.js")));
+        assert!(!is_test(Path::new("src/main.rs")));
+        // Only directories count; a file called `test` is just a file
+        assert!(!is_test(Path::new("bin/test")));
+        assert!(!is_test(Path::new("testsuite/runner.py")));
+    }
+}
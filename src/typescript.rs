@@ -0,0 +1,220 @@
+//! Token-aware minification for TypeScript (and TSX), which `minify-js` can't parse.
+//!
+//! Rather than building an AST, the source is scanned just well enough to recognize
+//! comments, string and template literals, and regular expressions, so their contents are
+//! never touched. Whitespace between other tokens is dropped where that can't join two
+//! tokens together, and line breaks are kept wherever automatic semicolon insertion might
+//! depend on them.
+
+/// Squeezes the whitespace out of `code`, also removing comments when `strip_comments` is set.
+pub fn minify(code: &str, strip_comments: bool) -> String {
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::with_capacity(code.len());
+    // Whitespace seen since the last token: None, or Some(contains a newline)
+    let mut pending: Option<bool> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            pending = Some(pending.unwrap_or(false) || c == '\n');
+            i += 1;
+            continue;
+        }
+
+        // Comments
+        if c == '/' && matches!(chars.get(i + 1), Some('/') | Some('*')) {
+            let line = chars[i + 1] == '/';
+            let end = if line {
+                find(&chars, i, "\n").unwrap_or(chars.len())
+            } else {
+                find(&chars, i + 2, "*/").map_or(chars.len(), |end| end + 2)
+            };
+            if strip_comments {
+                let newline = line || chars[i..end].contains(&'\n');
+                pending = Some(pending.unwrap_or(false) || newline);
+            } else {
+                flush_whitespace(&mut out, &mut pending, c);
+                out.extend(&chars[i..end]);
+                // Whatever follows a line comment must start on a new line
+                if line && end < chars.len() {
+                    out.push('\n');
+                }
+            }
+            i = end;
+            continue;
+        }
+
+        flush_whitespace(&mut out, &mut pending, c);
+        let end = match c {
+            '"' | '\'' => quoted_end(&chars, i, c),
+            '`' => template_end(&chars, i),
+            '/' if regex_allowed(&out) => regex_end(&chars, i),
+            _ => i + 1,
+        };
+        out.extend(&chars[i..end]);
+        i = end;
+    }
+
+    out
+}
+
+/// Emits whatever the skipped whitespace must become before the token starting with `next`:
+/// a line break where it might end a statement, a space where two tokens would otherwise
+/// merge, or nothing.
+fn flush_whitespace(out: &mut String, pending: &mut Option<bool>, next: char) {
+    if let Some(newline) = pending.take()
+        && let Some(prev) = out.chars().last().filter(|&prev| prev != '\n')
+    {
+        if newline && !"{([,;".contains(prev) && !")]},;".contains(next) {
+            out.push('\n');
+        } else if (is_word(prev) && is_word(next)) || (prev == next && "+-".contains(next)) {
+            out.push(' ');
+        }
+    }
+}
+
+/// Whether `c` can be part of an identifier, keyword, or number, so two of them need
+/// whitespace between them to stay separate tokens.
+pub fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$' || !c.is_ascii()
+}
+
+/// Index of the next occurrence of `pattern` at or after `from`.
+fn find(chars: &[char], from: usize, pattern: &str) -> Option<usize> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    (from..chars.len()).find(|&i| chars[i..].starts_with(&pattern))
+}
+
+/// End (exclusive) of the string literal opened by `quote` at `start`.
+fn quoted_end(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote || c == '\n' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// End (exclusive) of the template literal opened at `start`, including any nested
+/// `${ ... }` expressions with their own strings and templates.
+fn template_end(chars: &[char], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => return i + 1,
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                let mut depth = 0;
+                i += 1;
+                while i < chars.len() {
+                    match chars[i] {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        '"' | '\'' => {
+                            i = quoted_end(chars, i, chars[i]);
+                            continue;
+                        }
+                        '`' => {
+                            i = template_end(chars, i);
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// A `/` starts a regular expression rather than a division when it can't follow an
+/// operand: at the start, after an operator or opening bracket, or after keywords like
+/// `return`.
+fn regex_allowed(before: &str) -> bool {
+    let before = before.trim_end();
+    let Some(prev) = before.chars().last() else {
+        return true;
+    };
+    if !is_word(prev) {
+        return !")]}".contains(prev);
+    }
+    let word_start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(0, |(i, _)| i);
+    matches!(
+        &before[word_start..],
+        "return" | "typeof" | "instanceof" | "in" | "of" | "new" | "delete" | "void" | "throw"
+            | "case" | "do" | "else" | "yield" | "await"
+    )
+}
+
+/// End (exclusive) of the regular expression literal at `start`, including its flags.
+fn regex_end(chars: &[char], start: usize) -> usize {
+    let mut i = start + 1;
+    let mut in_class = false;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '/' if !in_class => {
+                i += 1;
+                while i < chars.len() && is_word(chars[i]) {
+                    i += 1;
+                }
+                return i;
+            }
+            // Not a regex after all (e.g. a division we misjudged); stop at the line end
+            '\n' => return start + 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    start + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_literals_survive_intact() {
+        let code = "const greeting = `Hello,   ${ user.name + `  (${ role })` }!\n  // not a comment`;\n";
+        assert_eq!(minify(code, true), "const greeting=`Hello,   ${ user.name + `  (${ role })` }!\n  // not a comment`;");
+    }
+
+    #[test]
+    fn regex_literals_survive_intact() {
+        let code = "const re = /ab\\/c [ /]+ d/gi;\nconst half = total / 2 / count;\n";
+        assert_eq!(minify(code, true), "const re=/ab\\/c [ /]+ d/gi;const half=total/2/count;");
+    }
+
+    #[test]
+    fn keeps_line_breaks_that_may_end_statements() {
+        let code = "let a = b\n(c || d).run()\nlet x = y\n+ +z\n";
+        assert_eq!(minify(code, false), "let a=b\n(c||d).run()\nlet x=y\n+ +z");
+    }
+
+    #[test]
+    fn strips_comments_only_when_asked() {
+        let code = "/** Doc */\nfunction f(x: number): number { // trailing\n  return x + 1; /* inline */\n}\n";
+        assert_eq!(minify(code, true), "function f(x:number):number{return x+1;}");
+        assert_eq!(minify(code, false), "/** Doc */\nfunction f(x:number):number{// trailing\nreturn x+1;/* inline */}");
+    }
+}
@@ -0,0 +1,132 @@
+//! Minimal processing for YAML, where indentation is structure and a stray edit can change
+//! what a file means.
+//!
+//! Lines are never joined or re-indented. The only changes are dropping `#` comments and
+//! squeezing runs of blank lines to one, both skipped inside block scalars (`key: |`), whose
+//! lines are content. Document markers (`---`, `...`) stay as they are, so multi-document
+//! files keep their documents apart.
+
+/// Processes `source` line by line, removing comments when `strip_comments` is set and
+/// collapsing runs of blank lines when `collapse_blank_lines` is.
+pub fn minify(source: &str, strip_comments: bool, collapse_blank_lines: bool) -> String {
+    let mut out = String::with_capacity(source.len());
+    // Indentation of the line that opened the block scalar we're inside
+    let mut block_scalar: Option<usize> = None;
+    // Quoted scalars may continue onto following lines
+    let mut quote: Option<char> = None;
+    let mut previous_blank = false;
+
+    for line in source.lines() {
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let blank = line.trim().is_empty();
+
+        if let Some(parent) = block_scalar {
+            if blank || indent > parent {
+                out.push_str(line);
+                out.push('\n');
+                previous_blank = false;
+                continue;
+            }
+            block_scalar = None;
+        }
+
+        if blank {
+            if !(collapse_blank_lines && previous_blank) {
+                out.push('\n');
+            }
+            previous_blank = true;
+            continue;
+        }
+
+        let uncommented = comment_start(line, &mut quote).map_or(line, |start| line[..start].trim_end());
+        let code = if strip_comments { uncommented } else { line };
+        // A line holding only a comment goes entirely
+        if code.trim().is_empty() {
+            continue;
+        }
+        if quote.is_none() && opens_block_scalar(uncommented) {
+            block_scalar = Some(indent);
+        }
+        out.push_str(code);
+        out.push('\n');
+        previous_blank = false;
+    }
+
+    // The last line break belongs to the code fence
+    if out.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+/// Byte offset of the `#` starting a comment on `line`, if any. A `#` only starts a comment
+/// outside quotes and at the start of the line or after whitespace, so `a#b` and URLs with
+/// fragments are left alone. Quotes only count at the start of a scalar, not as apostrophes
+/// in `don't`, and `quote` carries an unclosed one over to the next line.
+fn comment_start(line: &str, quote: &mut Option<char>) -> Option<usize> {
+    let mut previous = None;
+    // Last character that wasn't whitespace, to tell a quoted scalar from an apostrophe
+    let mut significant = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (*quote, c) {
+            // `''` is an escaped quote inside a single-quoted scalar
+            (Some('\''), '\'') if chars.peek().is_some_and(|&(_, next)| next == '\'') => {
+                chars.next();
+            }
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(open), c) if c == open => *quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') if significant.is_none_or(|c| ":-[{,?".contains(c)) => *quote = Some(c),
+            (None, '#') if previous.is_none_or(char::is_whitespace) => return Some(i),
+            (None, _) => {}
+        }
+        previous = Some(c);
+        if !c.is_whitespace() {
+            significant = Some(c);
+        }
+    }
+    None
+}
+
+/// Whether `code` ends with a block scalar indicator such as `|`, `>-`, or `|2+`, meaning
+/// the more indented lines after it are literal content.
+fn opens_block_scalar(code: &str) -> bool {
+    let code = code.trim_end();
+    let head = code.trim_end_matches(|c: char| c == '-' || c == '+' || c.is_ascii_digit());
+    let Some(before) = head.strip_suffix(['|', '>']) else {
+        return false;
+    };
+    before.is_empty() || before.ends_with([' ', '\t'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_scalars_keep_comments_and_blank_lines() {
+        let yaml = "script: |\n  # not a comment\n\n\n  echo hi  # still content\nnext: 1  # gone\n";
+        assert_eq!(minify(yaml, true, true), "script: |\n  # not a comment\n\n\n  echo hi  # still content\nnext: 1");
+    }
+
+    #[test]
+    fn folded_scalars_with_indicators_are_block_scalars() {
+        let yaml = "a: >-\n  # kept\nb: |2+\n  # kept\nc: '|'\n  # gone\n";
+        assert_eq!(minify(yaml, true, false), "a: >-\n  # kept\nb: |2+\n  # kept\nc: '|'");
+    }
+
+    #[test]
+    fn hashes_inside_quotes_and_words_are_not_comments() {
+        let yaml = "url: http://example.com/#top\nq: \"a # b\"\nr: 'it''s # here'\ns: don't # gone\n";
+        assert_eq!(minify(yaml, true, false), "url: http://example.com/#top\nq: \"a # b\"\nr: 'it''s # here'\ns: don't");
+    }
+
+    #[test]
+    fn quoted_scalars_continue_across_lines() {
+        let yaml = "msg: \"first\n  # inside the string\n  last\"\n# gone\n";
+        assert_eq!(minify(yaml, true, false), "msg: \"first\n  # inside the string\n  last\"");
+    }
+}
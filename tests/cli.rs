@@ -0,0 +1,1787 @@
+//! End-to-end checks that run the `cargo-prompt` binary on small projects written to a
+//! temporary directory.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+/// Writes `files`, given as `(relative path, contents)` pairs, to a new temporary directory.
+fn project(files: &[(&str, &str)]) -> TempDir {
+    let dir = tempfile::tempdir().expect("couldn't create a temporary directory");
+    for (path, contents) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+/// The `cargo-prompt` binary, with its cache kept under the target directory rather than
+/// the user's.
+fn cargo_prompt() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cargo-prompt"));
+    command.env("CARGO_PROMPT_CACHE_DIR", Path::new(env!("CARGO_TARGET_TMPDIR")).join("cache"));
+    command
+}
+
+/// Runs `cargo prompt <args>` inside `dir`, with the paths to include among `args`.
+fn run_on(dir: &Path, args: &[&str]) -> Output {
+    cargo_prompt()
+        .arg("prompt")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("couldn't run cargo-prompt")
+}
+
+/// Runs `cargo prompt <args> .` inside `dir`.
+fn run(dir: &Path, args: &[&str]) -> Output {
+    run_on(dir, &[args, &["."]].concat())
+}
+
+/// Runs `cargo prompt <args>` inside `dir`, expecting success, and returns stdout and stderr.
+fn prompt_on(dir: &Path, args: &[&str]) -> (String, String) {
+    let output = run_on(dir, args);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "cargo prompt {:?} failed:\n{}", args, stderr);
+    (stdout, stderr)
+}
+
+/// Runs `cargo prompt <args> .` inside `dir`, expecting success, and returns stdout and stderr.
+fn prompt(dir: &Path, args: &[&str]) -> (String, String) {
+    prompt_on(dir, &[args, &["."]].concat())
+}
+
+/// The same rough estimate cargo-prompt uses.
+fn tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[test]
+fn max_tokens_omits_whole_files_and_lists_them() {
+    let big = "x = 1\n".repeat(400);
+    let dir = project(&[("a.py", "a = 1\n"), ("b.py", &big), ("c.py", "c = 3\n")]);
+
+    for format in ["markdown", "xml"] {
+        let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--max-tokens", "300", "--format", format]);
+        assert!(tokens(&stdout) <= 300, "{} output is ~{} tokens", format, tokens(&stdout));
+        assert!(stdout.contains("a = 1") && stdout.contains("c = 3"));
+        assert!(!stdout.contains("x = 1"), "b.py should be left out whole");
+        assert!(stdout.contains("## Omitted files"));
+        assert!(stdout.contains("- b.py"));
+    }
+}
+
+#[test]
+fn jsonl_writes_one_record_per_file() {
+    let dir = project(&[("a.py", "a = 1\n"), ("b.py", "b = 2\nb += 1\n"), ("sub/c.py", "c = 3\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--format", "jsonl", "--per-file-stats"]);
+
+    let records: Vec<serde_json::Value> =
+        stdout.lines().map(|line| serde_json::from_str(line).expect("every line is a JSON object")).collect();
+    assert_eq!(records.len(), 3);
+    let b = records.iter().find(|record| record["path"] == "b.py").unwrap();
+    assert_eq!(b["language"], "python");
+    let content = b["content"].as_str().unwrap();
+    assert_eq!(b["lines"], content.lines().count());
+    assert_eq!(b["tokens"], tokens(content));
+}
+
+#[test]
+fn concat_includes_text_files_verbatim() {
+    let rust = "// keep me\nfn main() {\n    println!(\"hi\");\n}\n";
+    let dir = project(&[("notes.txt", "Some  notes\n\n\nhere\n"), ("src/main.rs", rust), ("crlf.txt", "one\r\ntwo\r\n")]);
+    fs::write(dir.path().join("image.bin"), [0x89, b'P', b'N', b'G', 0, 0, 1]).unwrap();
+
+    let (stdout, stderr) = prompt(dir.path(), &["--concat"]);
+    assert!(stdout.contains("## notes.txt\n```\nSome  notes\n\n\nhere\n"), "{}", stdout);
+    assert!(stdout.contains(&format!("## src/main.rs\n```rust\n{}", rust)), "{}", stdout);
+    assert!(stdout.contains("one\ntwo\n"), "CRLF line endings are normalized");
+    assert!(!stdout.contains("image.bin"));
+    assert!(!stderr.contains("image.bin"), "binary files are skipped quietly");
+}
+
+/// The contents of the first code block in `markdown` opened with ```` ```fence ````.
+fn code_block<'a>(markdown: &'a str, fence: &str) -> &'a str {
+    let start = markdown.find(&format!("```{}\n", fence)).expect("no such code block") + fence.len() + 4;
+    let end = start + markdown[start..].find("\n```").expect("unclosed code block");
+    &markdown[start..end]
+}
+
+#[test]
+fn line_numbers_match_the_source() {
+    let source = "# setup\n\nimport os\n\n\ndef main():\n    '''Entry point'''\n    print(os.getcwd())\n\n\n\nmain()\n";
+    let dir = project(&[("main.py", source)]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--line-numbers"]);
+
+    let numbered: Vec<&str> = code_block(&stdout, "python").lines().collect();
+    let original: Vec<&str> = source.lines().collect();
+    assert_eq!(numbered.len(), original.len());
+    for (i, (numbered, original)) in numbered.iter().zip(original).enumerate() {
+        assert_eq!(*numbered, format!("{:>2} | {}", i + 1, original));
+    }
+}
+
+#[test]
+fn line_numbers_reject_passes_that_move_lines() {
+    let dir = project(&[("main.py", "x = 1\n")]);
+    for conflicting in [&["-r"][..], &["--collapse-blank-lines"], &["--head", "5"], &["--trim-imports"]] {
+        let mut args = vec!["-p", "--no-minify", "--line-numbers"];
+        args.extend_from_slice(conflicting);
+        assert!(!run(dir.path(), &args).status.success(), "{:?} should be rejected", conflicting);
+    }
+}
+
+#[test]
+fn marker_keeps_matching_lines_with_context() {
+    let source = (1..=20)
+        .map(|i| if i == 6 || i == 15 { format!("line{} = {}  # PROMPT", i, i) } else { format!("line{} = {}", i, i) })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let dir = project(&[("main.py", &source), ("other.py", "nothing = 0\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--marker", "PROMPT", "--context", "2"]);
+
+    let expected = [
+        "line4 = 4",
+        "line5 = 5",
+        "line6 = 6  # PROMPT",
+        "line7 = 7",
+        "line8 = 8",
+        "...",
+        "line13 = 13",
+        "line14 = 14",
+        "line15 = 15  # PROMPT",
+        "line16 = 16",
+        "line17 = 17",
+    ];
+    assert_eq!(code_block(&stdout, "python"), expected.join("\n"));
+    assert!(!stdout.contains("other.py"), "files without a match are left out");
+}
+
+#[test]
+fn marker_windows_go_through_processing() {
+    let source = "a = 1\n# PROMPT: explain\nb = 2   # trailing\nc = 3\n";
+    let dir = project(&[("main.py", source)]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "-r", "--marker", "PROMPT", "--context", "1"]);
+    let block = code_block(&stdout, "python");
+    assert!(block.contains("a = 1") && block.contains("b = 2"), "{}", block);
+    assert!(!block.contains("trailing"), "comments are still removed with -r:\n{}", block);
+}
+
+#[test]
+fn head_and_tail_elide_the_middle() {
+    let source: String = (1..=50).map(|i| format!("value{} = {}\n", i, i)).collect();
+    let dir = project(&[("long.py", &source), ("short.py", "a = 1\nb = 2\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--head", "3", "--tail", "2"]);
+
+    assert!(stdout.contains("value1 = 1\nvalue2 = 2\nvalue3 = 3\n... 45 lines omitted ...\nvalue49 = 49\nvalue50 = 50"));
+    assert!(!stdout.contains("value4 ="));
+    assert!(stdout.contains("a = 1\nb = 2"), "short files are kept whole");
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--tail", "1"]);
+    assert!(stdout.contains("... 49 lines omitted ...\nvalue50 = 50"));
+}
+
+#[test]
+fn head_and_tail_cut_rust_at_item_boundaries() {
+    let mut source = String::from("use std::fmt;\n\n");
+    for i in 0..10 {
+        source.push_str(&format!("/// Doc {}\nfn f{}() -> u32 {{\n    {}\n}}\n\n", i, i, i));
+    }
+    let dir = project(&[("lib.rs", &source)]);
+
+    // Line 4 is inside `f0` and line 51 inside `f9`, so both functions are kept whole
+    let (stdout, _) = prompt(dir.path(), &["--no-minify", "--head", "4", "--tail", "3"]);
+    let block = code_block(&stdout, "rust");
+    assert!(block.starts_with("use std::fmt;\n\n/// Doc 0\nfn f0() -> u32 {\n    0\n}\n... "), "{}", block);
+    assert!(block.ends_with("/// Doc 9\nfn f9() -> u32 {\n    9\n}"), "{}", block);
+    assert!(!block.contains("fn f5"));
+
+    // Each part parses, so minifying doesn't report the file
+    let (stdout, stderr) = prompt(dir.path(), &["--head", "4", "--tail", "3"]);
+    assert!(!stderr.contains("could not be parsed"), "{}", stderr);
+    assert!(stdout.contains("lines omitted"));
+}
+
+#[test]
+fn warning_summary_lists_problem_files_by_kind() {
+    let dir = project(&[
+        ("src/good.rs", "fn good() {}\n"),
+        ("src/broken.rs", "fn broken( {\n"),
+        ("src/long.rs", &"// filler\n".repeat(20)),
+    ]);
+    fs::write(dir.path().join("src/latin1.rs"), b"// caf\xe9\nfn f() {}\n").unwrap();
+
+    let (_, stderr) = prompt(dir.path(), &["--encoding", "utf8", "--head", "5"]);
+    let parse = stderr.find("1 file(s) could not be parsed").expect(&stderr);
+    let not_utf8 = stderr.find("1 file(s) are not UTF-8 text and were skipped").expect(&stderr);
+    let too_large = stderr.find("1 file(s) are too large and were only partly included").expect(&stderr);
+    assert!(parse < not_utf8 && not_utf8 < too_large, "{}", stderr);
+    assert!(stderr.contains("broken.rs: "));
+    assert!(stderr.contains("latin1.rs: "));
+    assert!(stderr.contains("long.rs: 15 of 20 lines left out by --head/--tail"));
+    assert!(!stderr.contains("good.rs"));
+}
+
+#[test]
+fn max_files_stops_the_walk() {
+    let files: Vec<(String, String)> = (0..6).map(|i| (format!("f{}.py", i), format!("v{} = {}\n", i, i))).collect();
+    let files: Vec<(&str, &str)> = files.iter().map(|(path, contents)| (path.as_str(), contents.as_str())).collect();
+    let dir = project(&files);
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--max-files", "4", "--no-stats"]);
+    assert_eq!(stdout.matches("```python").count(), 4);
+    assert!(stderr.contains("Stopped walking at --max-files 4"), "{}", stderr);
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--max-files", "6"]);
+    assert_eq!(stdout.matches("```python").count(), 6);
+    assert!(!stderr.contains("--max-files"));
+}
+
+#[test]
+fn no_minify_keeps_multi_line_structure() {
+    let rust = "fn main() {\n    let x = 1;\n    if x > 0 {\n        println!(\"{}\", x);\n    }\n}";
+    let python = "def f(a):\n    if a:\n        return 1\n    return 2";
+    let dir = project(&[("src/main.rs", rust), ("a.py", python)]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    assert_eq!(code_block(&stdout, "rust").trim_end(), rust);
+    assert_eq!(code_block(&stdout, "python").trim_end(), python);
+}
+
+/// Runs `git <args>` inside `dir`, expecting success.
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("couldn't run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn branch_changes_include_only_files_changed_on_the_branch() {
+    let dir = project(&[("a.py", "a = 1\n"), ("b.py", "b = 1\n")]);
+    git(dir.path(), &["init", "-q", "-b", "main"]);
+    git(dir.path(), &["add", "-A"]);
+    git(dir.path(), &["commit", "-qm", "start"]);
+    git(dir.path(), &["checkout", "-qb", "feature"]);
+    fs::write(dir.path().join("b.py"), "b = 2\n").unwrap();
+    fs::write(dir.path().join("c.py"), "c = 1\n").unwrap();
+    git(dir.path(), &["add", "-A"]);
+    git(dir.path(), &["commit", "-qm", "feature"]);
+    // Changes on main after the branch point aren't the branch's
+    git(dir.path(), &["checkout", "-q", "main"]);
+    fs::write(dir.path().join("a.py"), "a = 2\n").unwrap();
+    git(dir.path(), &["commit", "-qam", "main"]);
+    git(dir.path(), &["checkout", "-q", "feature"]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--branch-changes", "main"]);
+    assert!(stdout.contains("## b.py") && stdout.contains("## c.py"), "{}", stdout);
+    assert!(!stdout.contains("## a.py"), "{}", stdout);
+}
+
+#[test]
+fn rust_that_syn_cannot_parse_is_included_raw() {
+    // Precise capturing syntax is newer than the parser
+    let source = "// note\nfn f() -> impl use<> Sized {\n    /* block */ let x = 1;\n}\n";
+    let dir = project(&[("lib.rs", source)]);
+
+    let (stdout, stderr) = prompt(dir.path(), &[]);
+    assert!(stdout.contains(source), "{}", stdout);
+    assert!(stderr.contains("1 file(s) could not be parsed"), "{}", stderr);
+    assert!(stderr.contains("lib.rs: "), "{}", stderr);
+
+    let (stdout, _) = prompt(dir.path(), &["-r"]);
+    assert!(stdout.contains("fn f() -> impl use<> Sized"), "{}", stdout);
+    assert!(!stdout.contains("note") && !stdout.contains("block"), "comments are removed with -r:\n{}", stdout);
+}
+
+#[test]
+fn summarize_large_keeps_only_signatures() {
+    let mut large = String::from("use std::fmt;\n\n");
+    for i in 0..20 {
+        large.push_str(&format!("pub fn f{}(x: u32) -> u32 {{\n    let y = x + {};\n    let z = y * 2;\n    z - 1\n}}\n\n", i, i));
+    }
+    let dir = project(&[("large.rs", &large), ("small.rs", "fn small() -> u32 {\n    let a = 1;\n    a + 1\n}\n")]);
+    let (stdout, _) = prompt(dir.path(), &["--no-minify", "--summarize-large", "500"]);
+
+    let large = stdout.find("## large.rs (summarized)").expect(&stdout);
+    let block = code_block(&stdout[large..], "rust");
+    assert!(block.contains("use std::fmt;"));
+    for i in 0..20 {
+        assert!(block.contains(&format!("pub fn f{}(x: u32) -> u32", i)), "{}", block);
+    }
+    assert!(!block.contains("z - 1"), "{}", block);
+    assert!(stdout.contains("## small.rs\n") && stdout.contains("a + 1"), "small files are kept whole");
+}
+
+#[test]
+fn several_directories_and_a_loose_file() {
+    let dir = project(&[
+        ("one/Cargo.toml", "[package]\nname = \"first\"\n"),
+        ("one/lib.rs", "pub fn one() {}\n"),
+        ("two/lib.rs", "pub fn two() {}\n"),
+        ("three/build.rs", "fn loose() {}\n"),
+        ("three/other.rs", "fn skipped() {}\n"),
+        ("three/.gitignore", "build.rs\n"),
+    ]);
+    let (stdout, _) = prompt_on(dir.path(), &["--no-minify", "one", "two", "three/build.rs"]);
+
+    assert!(stdout.starts_with("# first\n"), "the name comes from the first directory:\n{}", stdout);
+    assert!(stdout.contains("pub fn one()") && stdout.contains("pub fn two()"), "{}", stdout);
+    assert!(stdout.contains("fn loose()"), "loose files are included even if ignored:\n{}", stdout);
+    assert!(!stdout.contains("fn skipped()"), "{}", stdout);
+}
+
+#[test]
+fn with_deps_lists_the_manifest_dependencies() {
+    let manifest = "[package]\nname = \"alpha\"\nedition = \"2021\"\nrust-version = \"1.70\"\n\n[dependencies]\nserde = { workspace = true }\nregex = \"1.11\"\n\n[dev-dependencies]\ntempfile = \"3\"\n";
+    let dir = project(&[("Cargo.toml", manifest), ("src/lib.rs", "pub fn a() {}\n")]);
+    let (stdout, _) = prompt(dir.path(), &["--with-deps"]);
+
+    let expected = "# alpha\n## Dependencies\n- edition: 2021\n- rust-version: 1.70\n### [dependencies]\n- regex = 1.11\n- serde (workspace-inherited)\n### [dev-dependencies]\n- tempfile = 3\n";
+    assert!(stdout.starts_with(expected), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(!stdout.contains("## Dependencies"));
+}
+
+#[test]
+fn workspace_sections_name_their_crate() {
+    let dir = project(&[
+        ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+        ("crates/alpha/Cargo.toml", "[package]\nname = \"alpha\"\n"),
+        ("crates/alpha/src/lib.rs", "pub fn a() {}\n"),
+        ("crates/beta/Cargo.toml", "[package]\nname = \"beta\"\n"),
+        ("crates/beta/src/lib.rs", "pub fn b() {}\n"),
+    ]);
+    let (stdout, _) = prompt(dir.path(), &[]);
+
+    let workspace = dir.path().canonicalize().unwrap();
+    assert!(stdout.starts_with(&format!("# {}\n", workspace.file_name().unwrap().to_str().unwrap())), "{}", stdout);
+    assert!(stdout.contains("## crates/alpha/src/lib.rs (crate: alpha)"), "{}", stdout);
+    assert!(stdout.contains("## crates/beta/src/lib.rs (crate: beta)"), "{}", stdout);
+
+    // A single crate keeps plain headings
+    let (stdout, _) = prompt_on(dir.path(), &["crates/alpha"]);
+    assert!(stdout.starts_with("# alpha\n## src/lib.rs\n"), "{}", stdout);
+}
+
+#[test]
+fn hidden_includes_dotfiles() {
+    let dir = project(&[
+        ("visible.rs", "fn visible() {}\n"),
+        (".hidden.rs", "fn hidden() {}\n"),
+        (".config/tool.rs", "fn tool() {}\n"),
+        (".ignored.rs", "fn ignored() {}\n"),
+        (".gitignore", ".ignored.rs\n"),
+    ]);
+    git(dir.path(), &["init", "-q"]);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(stdout.contains("fn visible"));
+    assert!(!stdout.contains("fn hidden") && !stdout.contains("fn tool"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--hidden"]);
+    assert!(stdout.contains("## .hidden.rs") && stdout.contains("## .config/tool.rs"), "{}", stdout);
+    assert!(!stdout.contains("fn ignored"), ".gitignore still applies:\n{}", stdout);
+}
+
+#[test]
+fn no_gitignore_includes_ignored_files() {
+    let dir = project(&[
+        ("src/lib.rs", "pub fn lib() {}\n"),
+        ("src/generated.rs", "pub fn generated() {}\n"),
+        (".gitignore", "generated.rs\n"),
+    ]);
+    git(dir.path(), &["init", "-q"]);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(stdout.contains("fn lib"));
+    assert!(!stdout.contains("fn generated"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--no-gitignore"]);
+    assert!(stdout.contains("## src/generated.rs"), "{}", stdout);
+}
+
+#[test]
+fn depth_limits_the_walk() {
+    let dir = project(&[("top.py", "top = 0\n"), ("a/one.py", "one = 1\n"), ("a/b/two.py", "two = 2\n")]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--depth", "1"]);
+    assert!(stdout.contains("## top.py"));
+    assert!(!stdout.contains("one.py") && !stdout.contains("two.py"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--depth", "2"]);
+    assert!(stdout.contains("## top.py") && stdout.contains("## a/one.py"));
+    assert!(!stdout.contains("two.py"), "{}", stdout);
+}
+
+#[test]
+fn stats_footer_counts_the_fixture() {
+    let dir = project(&[("a.py", "a = 1\nb = 2\n"), ("sub/c.py", "c = 3\n"), ("main.rs", "fn main() {\n    run();\n}\n")]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    let footer = &stdout[stdout.find("## Stats\n").expect(&stdout)..];
+    assert!(footer.starts_with("## Stats\n- python: 2 file(s)\n- rust: 1 file(s)\n"), "{}", footer);
+    let total = "- Total: 3 file(s), 6 lines before minification, 43 bytes after, ~";
+    assert!(footer.contains(total), "{}", footer);
+    let estimate: usize = footer[footer.find(total).unwrap() + total.len()..]
+        .split(' ')
+        .next()
+        .and_then(|tokens| tokens.parse().ok())
+        .unwrap();
+    assert!(estimate > 0 && estimate <= tokens(&stdout), "{}", footer);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-stats"]);
+    assert!(!stdout.contains("## Stats"));
+    let (stdout, _) = prompt(dir.path(), &["-p", "--format", "jsonl"]);
+    assert!(!stdout.contains("Stats"));
+}
+
+#[test]
+fn keep_license_keeps_only_the_license_header() {
+    let source = "# Copyright 2024 Example Corp\n# Licensed under the MIT License\n\nimport os  # inline\n# helper\nprint(os.name)\n";
+    let dir = project(&[("main.py", source)]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "-r", "--keep-license"]);
+    let block = code_block(&stdout, "python");
+    assert!(block.starts_with("# Copyright 2024 Example Corp\n# Licensed under the MIT License\n"), "{}", block);
+    assert!(!block.contains("inline") && !block.contains("helper"), "{}", block);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "-r"]);
+    assert!(!stdout.contains("Copyright"));
+}
+
+#[test]
+fn notebook_code_cells_become_one_python_section() {
+    let notebook = r#"{"cells": [
+        {"cell_type": "code", "source": ["import math\n", "radius = 2"]},
+        {"cell_type": "markdown", "source": ["Compute the area"]},
+        {"cell_type": "code", "source": ["area = math.pi * radius ** 2\n"]}
+    ], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}"#;
+    let dir = project(&[("analysis.ipynb", notebook)]);
+
+    let (stdout, _) = prompt(dir.path(), &["--notebook", "--no-minify", "-r"]);
+    assert!(stdout.contains("## analysis.ipynb\n```python\n"), "{}", stdout);
+    let block = code_block(&stdout, "python");
+    assert!(block.contains("radius = 2") && block.contains("area = math.pi * radius ** 2"), "{}", block);
+    assert!(!block.contains("Compute the area"), "markdown cells go with -r:\n{}", block);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(!stdout.contains("analysis.ipynb"));
+}
+
+#[test]
+fn list_matches_the_files_a_normal_run_includes() {
+    let dir = project(&[
+        ("a.py", "a = 1\n"),
+        ("sub/c.py", "c = 3\n"),
+        ("main.rs", "fn main() {}\n"),
+        ("notes.txt", "not code\n"),
+    ]);
+
+    for extra in [&[][..], &["--flatten"], &["--path-prefix", "proj/"]] {
+        let (listed, _) = prompt(dir.path(), &[&["-p", "--list"], extra].concat());
+        let mut listed: Vec<(String, String)> = listed
+            .lines()
+            .map(|line| {
+                let (path, language) = line.split_once('\t').expect(line);
+                (path.to_string(), language.to_string())
+            })
+            .collect();
+        listed.sort();
+
+        let (stdout, _) = prompt(dir.path(), &[&["-p", "--format", "jsonl"], extra].concat());
+        let mut included: Vec<(String, String)> = stdout
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                (record["path"].as_str().unwrap().to_string(), record["language"].as_str().unwrap().to_string())
+            })
+            .collect();
+        included.sort();
+
+        assert_eq!(listed, included, "{:?}", extra);
+        assert_eq!(listed.len(), 3);
+    }
+}
+
+#[test]
+fn remove_docs_can_name_languages() {
+    let dir = project(&[("lib.rs", "/// Adds one\nfn add(x: u32) -> u32 {\n    x + 1\n}\n"), ("main.py", "# explain\nx = 1\n")]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--remove-docs=python"]);
+    assert!(code_block(&stdout, "rust").contains("/// Adds one"), "{}", stdout);
+    assert!(!code_block(&stdout, "python").contains("explain"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "-r"]);
+    assert!(!stdout.contains("Adds one") && !stdout.contains("explain"), "{}", stdout);
+
+    assert!(!run(dir.path(), &["--remove-docs=klingon"]).status.success());
+}
+
+#[test]
+fn headers_are_relative_to_the_walked_directory() {
+    let dir = project(&[("project/src/nested/deep.py", "deep = 1\n")]);
+    let nested = dir.path().join("project/src");
+
+    let (stdout, _) = prompt_on(&nested, &["-p", "../../project"]);
+    assert!(stdout.contains("## src/nested/deep.py\n"), "{}", stdout);
+
+    let (stdout, _) = prompt_on(&nested, &["-p", "--path-prefix", "project", "../../project"]);
+    assert!(stdout.contains("## project/src/nested/deep.py\n"), "{}", stdout);
+}
+
+#[test]
+fn dockerfiles_and_makefiles_are_matched_by_name() {
+    let dir = project(&[
+        ("Dockerfile", "FROM rust:1\n# build it\nRUN cargo build\n"),
+        ("docker/Dockerfile", "FROM alpine\n"),
+        ("Makefile", "all: build\n\nbuild:\n\tcargo build\n"),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &["--docker", "--make", "-r"]);
+    assert!(stdout.contains("## Dockerfile\n```dockerfile\nFROM rust:1\nRUN cargo build\n```"), "{}", stdout);
+    assert!(stdout.contains("## docker/Dockerfile\n```dockerfile\n"), "{}", stdout);
+    assert!(stdout.contains("## Makefile\n```makefile\n"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--docker"]);
+    assert!(stdout.contains("## Dockerfile") && !stdout.contains("## Makefile"), "{}", stdout);
+}
+
+#[test]
+fn configs_include_toml_yaml_and_json() {
+    let json = "{\n  \"a\": 1\n}";
+    let dir = project(&[
+        ("Cargo.toml", "[package]\nname = \"app\"\n"),
+        ("app.toml", "# comment\n[server]\nport = 80  # inline\n"),
+        ("app.yaml", "# comment\nkey: value  # inline\nlist:\n  - one\n"),
+        ("app.json", json),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &["--configs"]);
+    assert!(code_block(&stdout, "toml").contains("# comment\n[server]\nport = 80  # inline"), "{}", stdout);
+    assert_eq!(code_block(&stdout, "yaml"), "# comment\nkey: value  # inline\nlist:\n  - one");
+    assert_eq!(code_block(&stdout, "json").trim_end(), json);
+    assert!(!stdout.contains("## Cargo.toml"), "Cargo.toml is left to --with-deps:\n{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--configs", "-r"]);
+    let toml = code_block(&stdout, "toml");
+    assert!(toml.contains("[server]\nport = 80") && !toml.contains('#'), "{}", toml);
+    assert_eq!(code_block(&stdout, "yaml"), "key: value\nlist:\n  - one");
+    assert_eq!(code_block(&stdout, "json").trim_end(), json);
+}
+
+#[test]
+fn progress_stays_off_when_stderr_is_not_a_terminal() {
+    let dir = project(&[("a.py", "a = 1\n"), ("b.py", "b = 2\n")]);
+    let (plain, _) = prompt(dir.path(), &["-p"]);
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--progress"]);
+    assert_eq!(stdout, plain);
+    assert!(stderr.is_empty(), "{}", stderr);
+}
+
+/// Runs `cargo prompt <args>` over a project holding only `source` at `path`, and returns
+/// the contents of its ```` ```fence ```` block.
+fn processed(path: &str, source: &str, fence: &str, args: &[&str]) -> String {
+    let dir = project(&[(path, source)]);
+    let (stdout, _) = prompt(dir.path(), args);
+    assert!(stdout.contains(&format!("## {}\n", path)), "{} isn't included:\n{}", path, stdout);
+    code_block(&stdout, fence).to_string()
+}
+
+#[test]
+fn zig_line_comments_are_stripped() {
+    let source = "const std = @import(\"std\");\n// a comment\npub fn main() void {\n    std.debug.print(\"hi // not\\n\", .{});\n}\n";
+    let block = processed("main.zig", source, "zig", &["--zig", "--no-minify", "-r"]);
+    assert!(!block.contains("a comment"), "{}", block);
+    assert!(block.contains("\"hi // not\\n\""), "{}", block);
+}
+
+#[test]
+fn nim_block_comments_nest() {
+    let source = "proc f(x: int): int =\n  #[ outer #[ inner ]# still comment ]#\n  x + 1  # trailing\n";
+    let block = processed("main.nim", source, "nim", &["--nim", "--no-minify", "-r"]);
+    assert!(block.contains("proc f(x: int): int =") && block.contains("x + 1"), "{}", block);
+    assert!(!block.contains("still comment") && !block.contains("]#") && !block.contains("trailing"), "{}", block);
+}
+
+#[test]
+fn elixir_keeps_doc_attributes() {
+    let source = "defmodule M do\n  @doc \"\"\"\n  Adds one # not a comment\n  \"\"\"\n  # a comment\n  def f(x), do: x + 1\nend\n";
+    for file in ["m.ex", "m_test.exs"] {
+        let block = processed(file, source, "elixir", &["--elixir", "--no-minify", "-r"]);
+        assert!(block.contains("Adds one # not a comment"), "{}", block);
+        assert!(!block.contains("# a comment"), "{}", block);
+    }
+}
+
+#[test]
+fn all_includes_zig_nim_and_elixir() {
+    let dir = project(&[("a.zig", "const a = 1;\n"), ("b.nim", "let b = 2\n"), ("c.ex", "c = 3\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert!(stdout.contains("```zig\n") && stdout.contains("```nim\n") && stdout.contains("```elixir\n"), "{}", stdout);
+}
+
+#[test]
+fn extensionless_scripts_are_matched_by_shebang() {
+    let dir = project(&[
+        ("bin/tool", "#!/usr/bin/env python3\n# explain\nprint('hi')\n"),
+        ("bin/notes", "just some text\n"),
+    ]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "-r"]);
+    assert!(stdout.contains("## bin/tool\n```python\n"), "{}", stdout);
+    assert!(stdout.contains("print('hi')") && !stdout.contains("explain"), "{}", stdout);
+    assert!(!stdout.contains("bin/notes"));
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(!stdout.contains("bin/tool"), "Python isn't enabled:\n{}", stdout);
+}
+
+#[test]
+fn powershell_and_batch_comments_are_stripped() {
+    let powershell = "<#\n.SYNOPSIS\nDoes things\n#>\nfunction Get-X {\n    # a comment\n    Write-Output \"a # b\"\n}\n";
+    let block = processed("tool.ps1", powershell, "powershell", &["--powershell", "--no-minify", "-r"]);
+    assert!(!block.contains("SYNOPSIS") && !block.contains("#>") && !block.contains("a comment"), "{}", block);
+    assert!(block.contains("function Get-X {") && block.contains("Write-Output \"a # b\""), "{}", block);
+
+    let batch = "@echo off\r\nREM a remark\r\n:: another\r\necho REM kept\r\n";
+    let block = processed("build.bat", batch, "batch", &["--batch", "--no-minify", "-r"]);
+    assert!(!block.contains("remark") && !block.contains("another"), "{}", block);
+    assert!(block.contains("@echo off") && block.contains("echo REM kept"), "{}", block);
+}
+
+#[test]
+fn changed_since_includes_only_modified_files() {
+    let dir = project(&[("a.py", "a = 1\n"), ("b.py", "b = 1\n")]);
+    git(dir.path(), &["init", "-q"]);
+    git(dir.path(), &["add", "-A"]);
+    git(dir.path(), &["commit", "-qm", "start"]);
+    fs::write(dir.path().join("b.py"), "b = 2\n").unwrap();
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--changed-since", "HEAD"]);
+    assert!(stdout.contains("## b.py") && !stdout.contains("## a.py"), "{}", stdout);
+
+    let outside = project(&[("a.py", "a = 1\n")]);
+    let output = run(outside.path(), &["-p", "--changed-since", "HEAD"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not a git repository"));
+}
+
+#[test]
+fn vue_and_svelte_components_are_included_as_written() {
+    let vue = "<template>\n  <!-- greeting -->\n  <div   class=\"a\">{{ msg }}</div>\n</template>\n\n<script>\nexport default {\n  data() { return { msg: \"hi\" } }\n}\n</script>\n\n<style>\n.a {  color: red; }\n</style>";
+    assert_eq!(processed("App.vue", vue, "vue", &["--vue"]).trim_end(), vue);
+    let block = processed("App.vue", vue, "vue", &["--vue", "-r"]);
+    assert_eq!(block.trim_end(), vue.replace("  <!-- greeting -->\n", "  \n"));
+
+    let svelte = "<script>\n  let count = 0;\n</script>\n\n<button on:click={() => count++}>\n  {count}\n</button>";
+    assert_eq!(processed("Counter.svelte", svelte, "svelte", &["--svelte"]).trim_end(), svelte);
+}
+
+#[test]
+fn schema_comments_are_stripped() {
+    let proto = "syntax = \"proto3\";\n// line\n/* block */\nmessage A {\n  string url = 1; // trailing\n}\n";
+    assert_eq!(processed("a.proto", proto, "protobuf", &["--proto", "-r"]), "syntax=\"proto3\";message A{string url=1;}");
+
+    let graphql = "# comment\ntype Query {\n  \"Has # inside\"\n  a: Int\n}\n";
+    assert_eq!(processed("a.graphql", graphql, "graphql", &["--graphql", "-r"]), "type Query{\"Has # inside\"a:Int}");
+    assert!(processed("a.gql", graphql, "graphql", &["--graphql", "-r"]).starts_with("type Query"));
+
+    let thrift = "# hash\n// slash\n/* block */\nstruct A {\n  1: string b\n}\n";
+    assert_eq!(processed("a.thrift", thrift, "thrift", &["--thrift", "-r"]), "struct A{1:string b}");
+}
+
+#[test]
+fn all_includes_schema_files() {
+    let dir = project(&[("a.proto", "message A {}\n"), ("b.graphql", "type B { b: Int }\n"), ("c.thrift", "struct C {}\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert!(stdout.contains("```protobuf\n") && stdout.contains("```graphql\n") && stdout.contains("```thrift\n"), "{}", stdout);
+}
+
+#[test]
+fn group_by_language_sorts_files_under_language_headings() {
+    let dir = project(&[("src/z.rs", "fn z() {}\n"), ("src/a.rs", "fn a() {}\n"), ("py/b.py", "b = 1\n"), ("a.py", "a = 1\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--group-by", "language"]);
+
+    let order = ["# Python\n", "## a.py\n", "## py/b.py\n", "# Rust\n", "## src/a.rs\n", "## src/z.rs\n"];
+    let positions: Vec<usize> = order.iter().map(|heading| stdout.find(heading).expect(heading)).collect();
+    assert!(positions.is_sorted(), "{}", stdout);
+}
+
+#[test]
+fn ocaml_block_comments_nest() {
+    let source = "let f x =\n  (* outer (* inner *) still comment *)\n  x + 1\n";
+    assert_eq!(processed("a.ml", source, "ocaml", &["--ocaml", "-r"]), "let f x=x+1");
+    assert!(processed("a.mli", "val f : int -> int\n", "ocaml", &["--ocaml"]).contains("val f"));
+}
+
+#[test]
+fn fsharp_line_and_block_comments_are_stripped() {
+    let source = "// line\nlet f x =\n    (* block *)\n    x + 1\n";
+    assert_eq!(processed("a.fs", source, "fsharp", &["--fsharp", "-r"]), "let f x=x+1");
+}
+
+#[test]
+fn clojure_strings_and_reader_conditionals_are_kept() {
+    let source = "; comment\n(defn f [x]\n  #?(:clj \"a  ;  b\" :cljs   \"c\")\n  (+ x 1))\n";
+    let block = processed("a.cljc", source, "clojure", &["--clojure", "-r"]);
+    assert!(!block.contains("; comment"), "{}", block);
+    assert!(block.contains("#?(:clj \"a  ;  b\" :cljs   \"c\")"), "{}", block);
+}
+
+#[test]
+fn all_includes_ocaml_fsharp_and_clojure() {
+    let dir = project(&[("a.ml", "let a = 1\n"), ("b.fs", "let b = 2\n"), ("c.clj", "(def c 3)\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert!(stdout.contains("```ocaml\n") && stdout.contains("```fsharp\n") && stdout.contains("```clojure\n"), "{}", stdout);
+}
+
+#[test]
+fn exclude_ext_drops_extensions_within_all() {
+    let dir = project(&[("app.js", "let a = 1;\n"), ("vendor.min.js", "let v = 1;\n"), ("main.rs", "fn main() {}\n"), ("b.py", "b = 1\n")]);
+    for excluded in [&["--exclude-ext", "js"][..], &["--exclude-ext", ".JS"]] {
+        let mut args = vec!["-a"];
+        args.extend_from_slice(excluded);
+        let (stdout, _) = prompt(dir.path(), &args);
+        assert!(!stdout.contains("app.js") && !stdout.contains("vendor.min.js"), "{}", stdout);
+        assert!(stdout.contains("## main.rs") && stdout.contains("## b.py"), "{}", stdout);
+    }
+
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert!(stdout.contains("## app.js"), "{}", stdout);
+    let (stdout, _) = prompt(dir.path(), &["-a", "--exclude-ext", "js", "--exclude-ext", "py"]);
+    assert!(stdout.contains("## main.rs") && !stdout.contains("b.py"), "{}", stdout);
+}
+
+#[test]
+fn header_meta_shows_size_and_modification_date() {
+    let dir = project(&[("a.py", &"a = 1\n".repeat(1000))]);
+    // 2024-01-02 12:00:00 UTC
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_196_800);
+    fs::File::options().write(true).open(dir.path().join("a.py")).unwrap().set_modified(modified).unwrap();
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--header-meta"]);
+    assert!(stdout.contains("## a.py (5.9 KiB, modified 2024-01-02)\n"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p"]);
+    assert!(stdout.contains("## a.py\n"), "{}", stdout);
+}
+
+#[test]
+fn parent_gitignore_applies_to_a_walked_subdirectory() {
+    let dir = project(&[(".gitignore", "secret.py\n"), ("sub/secret.py", "token = 1\n"), ("sub/public.py", "name = 1\n")]);
+    git(dir.path(), &["init", "-q"]);
+
+    let (stdout, _) = prompt_on(dir.path(), &["-p", "sub"]);
+    assert!(stdout.contains("## public.py") && !stdout.contains("secret.py"), "{}", stdout);
+
+    let (stdout, _) = prompt_on(dir.path(), &["-p", "--no-gitignore", "sub"]);
+    assert!(stdout.contains("## secret.py"), "{}", stdout);
+}
+
+#[test]
+fn assembly_keeps_one_instruction_per_line() {
+    let nasm = "section .text\n    ; set up\n    mov   eax,  1    ; trailing\n    int 0x80\n";
+    assert_eq!(processed("start.asm", nasm, "asm", &["--asm", "-r"]), "section .text\nmov eax, 1\nint 0x80");
+
+    let gas = "# gas comment\n/* block */\n    movl  $1, %eax   # trailing\n    ret\n";
+    assert_eq!(processed("start.s", gas, "asm", &["--asm", "-r"]), "movl $1, %eax\nret");
+}
+
+#[test]
+fn shell_scripts_keep_their_line_breaks() {
+    let script = "#!/bin/sh\n# comment\nset -e\n\n   cd /tmp &&   ls\necho   \"a  b\"\nexit 0\n";
+    assert_eq!(processed("run.sh", script, "bash", &["--shell", "-r"]), "set -e\ncd /tmp && ls\necho \"a  b\"\nexit 0");
+}
+
+#[test]
+fn title_overrides_the_project_name() {
+    let dir = project(&[("Cargo.toml", "[package]\nname = \"from-cargo\"\n"), ("lib.rs", "fn f() {}\n")]);
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(stdout.starts_with("# from-cargo\n"), "{}", stdout);
+    let (stdout, _) = prompt(dir.path(), &["--title", "Review: parser rewrite"]);
+    assert!(stdout.starts_with("# Review: parser rewrite\n"), "{}", stdout);
+}
+
+#[test]
+fn prelude_comes_before_the_project_header() {
+    let dir = project(&[("lib.rs", "fn f() {}\n"), ("prelude.md", "Review this code.\nList any bugs.\n")]);
+
+    let (stdout, _) = prompt(dir.path(), &["--prelude", "You are reviewing the following codebase."]);
+    assert!(stdout.starts_with("You are reviewing the following codebase.\n\n# "), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--prelude-file", "prelude.md"]);
+    assert!(stdout.starts_with("Review this code.\nList any bugs.\n\n# "), "{}", stdout);
+
+    assert!(!run(dir.path(), &["--prelude", "a", "--prelude-file", "prelude.md"]).status.success());
+}
+
+#[test]
+fn erlang_comments_are_stripped_outside_strings() {
+    let source = "%% module doc\n-module(a).\nf(X) ->  \"100%  sure\" ++ X.  % trailing\n";
+    assert_eq!(processed("a.erl", source, "erlang", &["--erlang", "-r"]), "-module(a).f(X)->\"100%  sure\"++X.");
+}
+
+#[test]
+fn lisp_block_comments_nest() {
+    let source = ";; comment\n#| outer #| inner |# still comment |#\n(define (f x)  \"a ; b\"  (+ x 1))\n";
+    for file in ["a.scm", "a.lisp", "a.el"] {
+        let block = processed(file, source, "lisp", &["--lisp", "-r"]);
+        assert_eq!(block.trim(), "(define (f x)  \"a ; b\"  (+ x 1))");
+    }
+}
+
+#[test]
+fn all_includes_erlang_and_lisp() {
+    let dir = project(&[("a.erl", "-module(a).\n"), ("b.lisp", "(defun b () 2)\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert!(stdout.contains("```erlang\n") && stdout.contains("```lisp\n"), "{}", stdout);
+}
+
+#[test]
+fn per_file_stats_annotate_each_header() {
+    let source = "def f(x):\n    return x + 1\n\nprint(f(1))\n";
+    let dir = project(&[("a.py", source)]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--per-file-stats"]);
+    let content = code_block(&stdout, "python");
+    let expected = format!("## a.py\n<!-- lines: {}, tokens: {} -->\n```python\n", content.lines().count(), tokens(content));
+    assert!(stdout.contains(&expected), "{}", stdout);
+    assert!(expected.contains("lines: 4, tokens: 10"), "{}", expected);
+
+    // Counts are of the emitted content
+    let (stdout, _) = prompt(dir.path(), &["-p", "--per-file-stats"]);
+    let content = code_block(&stdout, "python");
+    assert!(stdout.contains(&format!("<!-- lines: {}, tokens: {} -->", content.lines().count(), tokens(content))), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p"]);
+    assert!(!stdout.contains("<!--"));
+}
+
+#[test]
+fn generated_files_are_skipped_unless_asked_for() {
+    let dir = project(&[
+        (".gitattributes", "schema.rs linguist-generated=true\n"),
+        ("src/schema.rs", "pub struct Generated;\n"),
+        ("src/lib.rs", "pub fn handwritten() {}\n"),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(stdout.contains("handwritten") && !stdout.contains("Generated"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--include-generated"]);
+    assert!(stdout.contains("## src/schema.rs"), "{}", stdout);
+}
+
+#[test]
+fn only_ext_is_a_strict_allowlist() {
+    let dir = project(&[("schema.sql", "SELECT 1; -- note\n"), ("main.rs", "fn main() {}\n"), ("a.py", "a = 1\n")]);
+    for only in ["sql", ".SQL"] {
+        let (stdout, _) = prompt(dir.path(), &["--only-ext", only]);
+        assert!(stdout.contains("## schema.sql"), "{}", stdout);
+        assert!(!stdout.contains("main.rs") && !stdout.contains("a.py"), "{}", stdout);
+    }
+
+    let (stdout, _) = prompt(dir.path(), &["-a", "--only-ext", "sql", "--only-ext", "rs"]);
+    assert!(stdout.contains("## schema.sql") && stdout.contains("## main.rs") && !stdout.contains("a.py"), "{}", stdout);
+}
+
+#[test]
+fn no_rust_leaves_out_rust_files() {
+    let dir = project(&[("main.rs", "fn main() {}\n"), ("a.py", "a = 1\n")]);
+    let (stdout, _) = prompt(dir.path(), &["--no-rust", "-p"]);
+    assert!(stdout.contains("## a.py") && !stdout.contains("main.rs") && !stdout.contains("```rust"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p"]);
+    assert!(stdout.contains("## main.rs"), "Rust stays on by default:\n{}", stdout);
+}
+
+#[test]
+fn typescript_template_literals_survive_minifying() {
+    let source = "// greet\nexport function greet(user: User): string {\n  return `Hello,   ${user.name}!\n  Welcome back.`;\n}\n";
+    let block = processed("greet.ts", source, "typescript", &["-t", "-r"]);
+    assert_eq!(block, "export function greet(user:User):string{return`Hello,   ${user.name}!\n  Welcome back.`;}");
+}
+
+#[test]
+fn latin1_files_are_included_by_encoding() {
+    let dir = project(&[]);
+    fs::write(dir.path().join("cafe.py"), b"name = 'caf\xe9'\n").unwrap();
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--no-minify"]);
+    assert!(stdout.contains("name = 'caf\u{fffd}'"), "{}", stdout);
+    assert!(stderr.contains("1 file(s) are not UTF-8 text; invalid bytes were replaced"), "{}", stderr);
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--no-minify", "--encoding", "latin1"]);
+    assert!(stdout.contains("name = 'café'"), "{}", stdout);
+    assert!(stderr.is_empty(), "{}", stderr);
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--encoding", "utf8"]);
+    assert!(!stdout.contains("cafe.py"));
+    assert!(stderr.contains("are not UTF-8 text and were skipped"), "{}", stderr);
+}
+
+#[test]
+fn fixed_form_comments_are_stripped_by_column() {
+    let fortran = "C     FIXED FORM COMMENT\n*     ANOTHER\n      PROGRAM HELLO\n      PRINT *, 'C*'\n      END\n";
+    let block = processed("hello.f", fortran, "fortran", &["--fortran", "-r"]);
+    assert_eq!(block.trim(), "PROGRAM HELLO\n      PRINT *, 'C*'\n      END");
+
+    let cobol = "000100 IDENTIFICATION DIVISION.\n000200*COMMENT LINE\n000300 PROGRAM-ID. HELLO.\n000400     DISPLAY \"A*B\".\n";
+    let block = processed("hello.cob", cobol, "cobol", &["--cobol", "-r"]);
+    assert_eq!(block.trim(), "000100 IDENTIFICATION DIVISION.\n\n000300 PROGRAM-ID. HELLO.\n000400     DISPLAY \"A*B\".");
+}
+
+#[test]
+fn free_form_fortran_and_pascal_comments_are_stripped() {
+    let fortran = "! modern\nprogram hi\n  print *, \"a ! b\"  ! trailing\nend program\n";
+    assert_eq!(processed("hi.f90", fortran, "fortran", &["--fortran", "-r"]), "program hi\nprint *, \"a ! b\"\nend program");
+
+    let pascal = "{ brace }\n(* paren *)\nprogram A; // line\nbegin\n  writeln('x { y }');\nend.\n";
+    assert_eq!(processed("a.pas", pascal, "pascal", &["--pascal", "-r"]), "program A;\nbegin\nwriteln('x { y }');\nend.");
+}
+
+#[test]
+fn flatten_numbers_repeated_file_names() {
+    let dir = project(&[("a/mod.rs", "fn a() {}\n"), ("b/mod.rs", "fn b() {}\n"), ("b/util.rs", "fn util() {}\n")]);
+    let (stdout, _) = prompt(dir.path(), &["--flatten", "--no-minify"]);
+
+    assert!(stdout.contains("## mod.rs\n") && stdout.contains("## mod.rs (2)\n") && stdout.contains("## util.rs\n"), "{}", stdout);
+    assert!(!stdout.contains("a/") && !stdout.contains("b/"), "{}", stdout);
+    assert!(stdout.contains("fn a()") && stdout.contains("fn b()"));
+}
+
+#[test]
+fn streamed_output_matches_buffered_output() {
+    let dir = project(&[
+        ("Cargo.toml", "[package]\nname = \"streamed\"\n"),
+        ("src/main.rs", "fn main() {\n    println!(\"<&>\");\n}\n"),
+        ("src/lib.rs", "pub fn add(a: u32, b: u32) -> u32 {\n    a + b\n}\n"),
+        ("tools/gen.py", "print('hi')\n"),
+    ]);
+    for format in ["markdown", "xml", "jsonl"] {
+        let (streamed, _) = prompt(dir.path(), &["-p", "--format", format]);
+        // A token budget needs every section at once, so the output is buffered
+        let (buffered, _) = prompt(dir.path(), &["-p", "--format", format, "--max-tokens", "1000000"]);
+        assert_eq!(streamed, buffered, "{} output differs", format);
+    }
+}
+
+#[test]
+fn comment_report_lists_ratios_least_documented_first() {
+    // Whitespace isn't counted: 3 comment characters ("#ab") to 8 code characters ("total=1;")
+    let dir = project(&[("half.py", "# ab\ntotal = 1;\n"), ("bare.py", "y = 22\n"), ("heavy.py", "# a long comment\nz = 1\n")]);
+    let (_, stderr) = prompt(dir.path(), &["-p", "--comment-report"]);
+
+    let expected = "Comment density (comment / code characters), least documented first:\n  0.00  bare.py\n  0.38  half.py\n  4.33  heavy.py\n";
+    assert!(stderr.contains(expected), "{}", stderr);
+}
+
+#[test]
+fn comment_report_includes_files_read_with_another_encoding() {
+    let dir = project(&[]);
+    fs::write(dir.path().join("cafe.py"), b"# caf\xe9\nname = 1\n").unwrap();
+
+    for encoding in ["lossy", "latin1"] {
+        let (stdout, stderr) = prompt(dir.path(), &["-p", "--comment-report", "--encoding", encoding]);
+        assert!(stdout.contains("## cafe.py"), "{}", stdout);
+        assert!(stderr.contains("  0.83  cafe.py\n"), "{}: {}", encoding, stderr);
+    }
+}
+
+#[test]
+fn a_mistyped_subcommand_names_the_right_one() {
+    let dir = project(&[("a.py", "a = 1\n")]);
+    let output = cargo_prompt()
+        .args(["promt", "."])
+        .current_dir(dir.path())
+        .output()
+        .expect("couldn't run cargo-prompt");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("promt does not exist"), "{}", stderr);
+    assert!(stderr.contains("the subcommand is `prompt`"), "{}", stderr);
+
+    // A missing path that is not in the subcommand's place gets no such hint
+    for args in [&["nowhere"][..], &[".", "nowhere"]] {
+        let output = run_on(dir.path(), args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("nowhere does not exist") && !stderr.contains("subcommand"), "{}", stderr);
+    }
+}
+
+#[test]
+fn xml_output_is_well_formed_and_escaped() {
+    let source = "fn main() {\n    if 1 < 2 && true {\n        println!(\"<b>\");\n    }\n}\n";
+    let dir = project(&[("src/a&b.rs", source), ("x.py", "print('<x/>')\n")]);
+    let (stdout, _) = prompt(dir.path(), &["--no-minify", "--format", "xml", "--title", "R&D <tools>"]);
+
+    assert!(stdout.contains("1 &lt; 2 &amp;&amp; true"), "{}", stdout);
+    let document = roxmltree::Document::parse(&stdout).expect("the output is well-formed XML");
+    let project = document.root_element();
+    assert_eq!(project.tag_name().name(), "project");
+    assert_eq!(project.attribute("name"), Some("R&D <tools>"));
+
+    let file = project
+        .children()
+        .find(|node| node.attribute("path") == Some("src/a&b.rs"))
+        .expect("a <file> element for src/a&b.rs");
+    assert_eq!(file.attribute("lang"), Some("rust"));
+    assert_eq!(file.text().unwrap().trim(), source.trim());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn gz_output_decompresses_to_the_markdown() {
+    use std::io::Read;
+
+    let dir = project(&[("a.py", "a = 1\n"), ("b.py", "b = 2\n")]);
+    let (plain, _) = prompt(dir.path(), &["-p"]);
+    prompt(dir.path(), &["-p", "--output", "prompt.md.gz"]);
+
+    let mut markdown = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(dir.path().join("prompt.md.gz")).unwrap())
+        .read_to_string(&mut markdown)
+        .expect("the output is gzip");
+    assert_eq!(markdown, plain);
+
+    prompt(dir.path(), &["-p", "--output", "prompt.md.zst"]);
+    let markdown = zstd::decode_all(fs::File::open(dir.path().join("prompt.md.zst")).unwrap()).unwrap();
+    assert_eq!(String::from_utf8(markdown).unwrap(), plain);
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+fn gz_output_needs_the_compression_feature() {
+    let dir = project(&[("a.py", "a = 1\n")]);
+    let output = run(dir.path(), &["--output", "prompt.md.gz"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("`compression` feature"));
+    assert!(!dir.path().join("prompt.md.gz").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_symlinks_includes_linked_directories() {
+    let dir = project(&[("project/a.py", "a = 1\n"), ("shared/linked.py", "linked = 1\n")]);
+    std::os::unix::fs::symlink(dir.path().join("shared"), dir.path().join("project/shared")).unwrap();
+    // A link back up the tree must not make the walk loop forever
+    std::os::unix::fs::symlink(dir.path().join("project"), dir.path().join("project/loop")).unwrap();
+    let project = dir.path().join("project");
+
+    let (stdout, _) = prompt(&project, &["-p", "--no-minify"]);
+    assert!(stdout.contains("a = 1") && !stdout.contains("linked = 1"), "{}", stdout);
+
+    let (stdout, _) = prompt(&project, &["-p", "--no-minify", "--follow-symlinks"]);
+    assert!(stdout.contains("linked = 1"), "{}", stdout);
+    assert_eq!(stdout.matches("a = 1").count(), 1, "the loop is walked once:\n{}", stdout);
+}
+
+#[test]
+fn cuda_comments_are_stripped() {
+    let source = "// kernel\n__global__ void add(int *a) {\n    /* index */ a[threadIdx.x] += 1;\n    printf(\"// kept\");\n}\n";
+    for file in ["add.cu", "add.cuh"] {
+        let block = processed(file, source, "cuda", &["--cuda", "--no-minify", "-r"]);
+        assert!(block.contains("__global__ void add(int *a)") && block.contains("\"// kept\""), "{}", block);
+        assert!(!block.contains("kernel") && !block.contains("index"), "{}", block);
+    }
+}
+
+#[test]
+fn glsl_comments_are_stripped() {
+    let source = "#version 450\n// shade\nvoid main() {\n    /* red */ gl_FragColor = vec4(1.0);\n}\n";
+    for file in ["a.glsl", "a.vert", "a.frag", "a.comp"] {
+        let block = processed(file, source, "glsl", &["--glsl", "--no-minify", "-r"]);
+        assert!(block.contains("#version 450") && block.contains("gl_FragColor = vec4(1.0);"), "{}", block);
+        assert!(!block.contains("shade") && !block.contains("red"), "{}", block);
+    }
+
+    let block = processed("a.frag", source, "glsl", &["--glsl", "-r"]);
+    assert!(block.starts_with("#version 450\n"), "the directive keeps its own line:\n{}", block);
+}
+
+#[test]
+fn hlsl_comments_are_stripped() {
+    let source = "// shade\nfloat4 main() : SV_Target {\n    /* red */ return float4(1, 0, 0, 1);\n}\n";
+    for file in ["a.hlsl", "a.fx"] {
+        let block = processed(file, source, "hlsl", &["--hlsl", "--no-minify", "-r"]);
+        assert!(block.contains("float4 main() : SV_Target") && block.contains("return float4(1, 0, 0, 1);"), "{}", block);
+        assert!(!block.contains("shade") && !block.contains("red"), "{}", block);
+    }
+}
+
+#[test]
+fn all_includes_cuda_glsl_and_hlsl() {
+    let dir = project(&[("a.cu", "int a;\n"), ("b.frag", "void main() {}\n"), ("c.hlsl", "float c;\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert!(stdout.contains("```cuda\n") && stdout.contains("```glsl\n") && stdout.contains("```hlsl\n"), "{}", stdout);
+}
+
+#[test]
+fn dedupe_collapses_identical_files_into_a_note() {
+    let stub = "# Licensed under MIT\nVERSION = 1\n";
+    let dir = project(&[
+        ("a/__init__.py", stub),
+        ("b/__init__.py", stub),
+        ("c.py", "c = 3\n"),
+        ("d.py", "# Licensed under MIT\nVERSION = 2\n"),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--dedupe"]);
+    assert_eq!(stdout.matches("VERSION = 1").count(), 1, "{}", stdout);
+    // Whichever copy the walk reaches first is kept
+    let (first, second) = if stdout.find("## a/").unwrap() < stdout.find("## b/").unwrap() { ("a", "b") } else { ("b", "a") };
+    assert!(stdout.contains(&format!("## {}/__init__.py\n```python\n", first)), "{}", stdout);
+    let note = format!("## {}/__init__.py\nSame content as `{}/__init__.py`.\n", second, first);
+    assert!(stdout.contains(&note), "{}", stdout);
+    assert!(stdout.contains("c = 3"));
+    assert!(stdout.contains("## d.py\n```python\n# Licensed under MIT\nVERSION = 2\n"), "a near copy is kept:\n{}", stdout);
+    assert_eq!(stdout.matches("Same content as").count(), 1);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    assert_eq!(stdout.matches("VERSION = 1").count(), 2, "without --dedupe both copies are kept");
+}
+
+#[test]
+fn config_file_sets_defaults_the_command_line_overrides() {
+    let dir = project(&[
+        (".cargo-prompt.toml", "python = true\nno-minify = true\nformat = \"xml\"\n"),
+        ("a.py", "a = 1\n"),
+        ("b.pyw", "b = 2\n"),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(stdout.contains("<file path=\"a.py\" lang=\"python\">\na = 1\n"), "{}", stdout);
+    assert!(stdout.contains("b = 2"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--format", "markdown", "--exclude-ext", "pyw"]);
+    assert!(stdout.contains("## a.py\n```python\na = 1\n"), "{}", stdout);
+    assert!(!stdout.contains("b = 2"), "{}", stdout);
+
+    // --config reads another file in place of the project's
+    fs::write(dir.path().join("other.toml"), "exclude-ext = [\"pyw\"]\n").unwrap();
+    let (stdout, _) = prompt(dir.path(), &["-p", "--config", "other.toml"]);
+    assert!(stdout.contains("## a.py\n") && !stdout.contains("b.pyw"), "{}", stdout);
+}
+
+#[test]
+fn command_line_switches_off_and_replaces_config_file_options() {
+    let dir = project(&[
+        (".cargo-prompt.toml", "python = true\nno-minify = true\nexclude-ext = [\"pyw\"]\n"),
+        ("a.py", "a  =  1\n"),
+        ("b.pyw", "b = 2\n"),
+        ("c.js", "let c = 3;\n"),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &["--no-python", "-j"]);
+    assert!(stdout.contains("## c.js\n") && !stdout.contains("```python"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--minify"]);
+    assert_eq!(code_block(&stdout, "python"), "a=1");
+
+    // A list on the command line replaces the file's
+    let (stdout, _) = prompt(dir.path(), &["--exclude-ext", "js"]);
+    assert!(stdout.contains("## a.py\n") && stdout.contains("## b.pyw\n"), "{}", stdout);
+}
+
+#[test]
+fn trim_imports_drops_only_top_level_use_items() {
+    let source = "//! Crate docs\nuse std::fmt;\nuse std::io::{self, Write};\n\nmod inner {\n    use std::fmt;\n}\n\npub fn f() -> io::Result<()> {\n    use std::fs;\n    Ok(())\n}\n";
+    let block = processed("src/lib.rs", source, "rust", &["--no-minify", "--trim-imports"]);
+    assert!(block.starts_with("//! Crate docs\nmod inner {\n    use std::fmt;\n}\n"), "{}", block);
+    assert!(!block.contains("use std::io"), "{}", block);
+    assert!(block.contains("pub fn f() -> io::Result<()> {\n    use std::fs;"), "{}", block);
+
+    let block = processed("src/lib.rs", source, "rust", &["--no-minify", "--trim-imports", "-r"]);
+    assert!(!block.contains("Crate docs") && !block.contains("use std::io"), "{}", block);
+}
+
+#[test]
+fn outline_lists_public_items() {
+    let source = "pub fn open() {}\nfn private() {}\npub struct Config;\npub enum Mode { A }\npub trait Run {}\npub mod inner { pub fn nested() {} }\n";
+    let dir = project(&[("src/lib.rs", source)]);
+    let outline = "## src/lib.rs\n- `fn open`\n- `struct Config`\n- `enum Mode`\n- `trait Run`\n- `mod inner`\n- `fn inner::nested`\n";
+
+    let (stdout, _) = prompt(dir.path(), &["--outline"]);
+    assert!(stdout.contains(&format!("{}```rust\n", outline)), "{}", stdout);
+    assert!(code_block(&stdout, "rust").contains("private"));
+
+    let (stdout, _) = prompt(dir.path(), &["--outline=only"]);
+    assert!(stdout.contains(outline) && !stdout.contains("```rust"), "{}", stdout);
+    assert!(!stdout.contains("private"));
+}
+
+#[test]
+fn toc_links_every_file_section() {
+    let dir = project(&[("src/main.rs", "fn main() {}\n"), ("My Script.py", "x = 1\n"), ("a_b.py", "y = 2\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-a", "--toc"]);
+    assert!(stdout.starts_with("# Unnamed Project\n## Contents\n"), "{}", stdout);
+
+    for (path, anchor) in [("src/main.rs", "srcmainrs"), ("My Script.py", "my-scriptpy"), ("a_b.py", "a_bpy")] {
+        assert!(stdout.contains(&format!("- [{}](#{})\n", path, anchor)), "{}", stdout);
+        assert!(stdout.contains(&format!("\n## {}\n", path)), "{}", stdout);
+    }
+    let entries = stdout.lines().filter(|line| line.starts_with("- [")).count();
+    let sections = stdout.lines().filter(|line| line.starts_with("## ") && line != &"## Contents" && line != &"## Stats").count();
+    assert_eq!(entries, sections);
+}
+
+#[test]
+fn files_from_processes_exactly_the_listed_files() {
+    let dir = project(&[
+        (".gitignore", "build/\n"),
+        ("a.py", "a = 1\n"),
+        ("build/b.py", "b = 2\n"),
+        ("c.py", "c = 3\n"),
+        ("list.txt", "a.py\n\n  build/b.py  \n"),
+    ]);
+    git(dir.path(), &["init", "-q"]);
+    let only_listed = |stdout: &str| {
+        assert!(stdout.contains("a = 1") && stdout.contains("b = 2"), "ignore rules don't apply:\n{}", stdout);
+        assert!(!stdout.contains("c = 3"), "{}", stdout);
+    };
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    assert!(!stdout.contains("b = 2"), "a walk leaves out the ignored file:\n{}", stdout);
+
+    let (stdout, _) = prompt_on(dir.path(), &["-p", "--no-minify", "--files-from", "list.txt"]);
+    only_listed(&stdout);
+
+    let mut child = cargo_prompt()
+        .args(["prompt", "-p", "--no-minify", "--files-from", "-"])
+        .current_dir(dir.path())
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("couldn't run cargo-prompt");
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), b"a.py\nbuild/b.py\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    only_listed(&String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn strip_attributes_removes_derives_and_keeps_the_struct() {
+    let source = "#[derive(Debug, Clone)]\n#[repr(C)]\npub struct Point {\n    pub x: i32,\n}\n";
+    let block = processed("src/lib.rs", source, "rust", &["--no-minify", "--strip-attributes"]);
+    assert_eq!(block, "pub struct Point {\n    pub x: i32,\n}\n");
+
+    let block = processed("src/lib.rs", source, "rust", &["--no-minify", "--strip-attributes=derive"]);
+    assert_eq!(block, "#[repr(C)]\npub struct Point {\n    pub x: i32,\n}\n");
+}
+
+#[test]
+fn a_missing_directory_is_an_error() {
+    let dir = project(&[("a.rs", "fn a() {}\n")]);
+    let output = run_on(dir.path(), &["missing/src"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty(), "no header is printed for a failed run");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("missing/src does not exist"));
+}
+
+#[test]
+fn no_matching_files_is_warned_about() {
+    let dir = project(&[("a.py", "a = 1\n"), ("notes.txt", "text\n")]);
+    let (stdout, stderr) = prompt(dir.path(), &[]);
+    assert!(stdout.starts_with("# Unnamed Project\n"));
+    assert!(stderr.contains("no matching files found") && stderr.contains("-p (Python)"), "{}", stderr);
+
+    let (_, stderr) = prompt(dir.path(), &["-p"]);
+    assert!(!stderr.contains("no matching files"), "{}", stderr);
+}
+
+#[test]
+fn minify_levels_differ_but_keep_the_same_code() {
+    let source = "// note\nfn add(a: i32,   b: i32) -> i32 {\n\n    a + b // sum\n}\n\nfn main() { add(1, 2); }\n";
+    let tokens = |code: &str| code.parse::<proc_macro2::TokenStream>().unwrap().to_string();
+
+    let none = processed("a.rs", source, "rust", &["--minify-level", "none"]);
+    let light = processed("a.rs", source, "rust", &["--minify-level", "light"]);
+    let full = processed("a.rs", source, "rust", &["--minify-level", "full"]);
+    assert_eq!(none, source);
+    assert_eq!(light, "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\nfn main() {\n    add(1, 2);\n}\n");
+    assert_eq!(full.lines().count(), 1, "{}", full);
+
+    for code in [&none, &light, &full] {
+        syn::parse_file(code).unwrap_or_else(|e| panic!("{}\n{}", e, code));
+        assert_eq!(tokens(code), tokens(source));
+    }
+}
+
+#[test]
+fn split_by_dir_writes_one_prompt_per_top_directory() {
+    let dir = project(&[
+        ("Cargo.toml", "[package]\nname = \"demo\"\n"),
+        ("build.rs", "fn main() {}\n"),
+        ("src/lib.rs", "pub fn lib() {}\n"),
+        ("src/util/mod.rs", "pub fn util() {}\n"),
+        ("tests/it.rs", "fn it() {}\n"),
+    ]);
+    let (stdout, _) = prompt(dir.path(), &["--split-by-dir", "prompts"]);
+    assert!(stdout.is_empty(), "{}", stdout);
+
+    let mut written: Vec<String> = fs::read_dir(dir.path().join("prompts"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    written.sort();
+    assert_eq!(written, ["root-prompt.md", "src-prompt.md", "tests-prompt.md"]);
+
+    let read = |name: &str| fs::read_to_string(dir.path().join("prompts").join(name)).unwrap();
+    let src = read("src-prompt.md");
+    assert!(src.starts_with("# demo\n"), "{}", src);
+    assert!(src.contains("## src/lib.rs\n") && src.contains("## src/util/mod.rs\n"), "{}", src);
+    assert!(!src.contains("fn it") && !src.contains("build.rs"), "{}", src);
+    let tests = read("tests-prompt.md");
+    assert!(tests.contains("## tests/it.rs\n") && !tests.contains("fn lib"), "{}", tests);
+    assert!(read("root-prompt.md").contains("## build.rs\n"));
+}
+
+#[test]
+fn expand_macros_expands_println_or_explains_what_is_missing() {
+    let dir = project(&[
+        ("Cargo.toml", "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+        ("src/main.rs", "fn main() {\n    println!(\"hi\");\n}\n"),
+    ]);
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let installed = Command::new(cargo).args(["expand", "--version"]).output().is_ok_and(|output| output.status.success());
+
+    let output = run(dir.path(), &["--expand-macros"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    if installed {
+        assert!(output.status.success(), "{}", stderr);
+        assert!(stdout.contains("## src/main.rs (macros expanded)\n"), "{}", stdout);
+        assert!(!stdout.contains("println !") && stdout.contains("_print"), "{}", stdout);
+    } else {
+        assert!(!output.status.success());
+        assert!(stderr.contains("cargo install cargo-expand"), "{}", stderr);
+    }
+}
+
+#[test]
+fn bom_and_crlf_are_normalized() {
+    let dir = project(&[("a.py", "\u{feff}import os\r\nx = 1\r\n"), ("b.rs", "\u{feff}fn b() {}\r\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    assert!(!stdout.contains('\u{feff}') && !stdout.contains('\r'), "{:?}", stdout);
+    assert!(stdout.contains("```python\nimport os\nx = 1\n"), "{}", stdout);
+    assert!(stdout.contains("```rust\nfn b() {}\n"), "{}", stdout);
+}
+
+#[test]
+fn respect_editorconfig_converts_tabs_to_spaces() {
+    let source = "def f(x):\n\tif x:\n\t\treturn 1\n\treturn 2\n";
+    let dir = project(&[
+        (".editorconfig", "root = true\n\n[*.py]\nindent_style = space\nindent_size = 4\n"),
+        ("a.py", source),
+        ("b.js", "function f() {\n\treturn 1;\n}\n"),
+    ]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "-j", "--no-minify", "--respect-editorconfig"]);
+    assert_eq!(code_block(&stdout, "python"), "def f(x):\n    if x:\n        return 1\n    return 2\n");
+    assert!(stdout.contains("\treturn 1;"), "the .js file has no indent_style:\n{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    assert_eq!(code_block(&stdout, "python"), source);
+}
+
+#[test]
+fn wrap_breaks_long_lines_at_the_width() {
+    let source = "fn f() -> u32 {\n    let total = alpha + beta + gamma + delta + epsilon;\n    total\n}\n";
+    let dir = project(&[("a.rs", source)]);
+    let (stdout, _) = prompt(dir.path(), &["--no-minify", "--wrap", "40"]);
+    let block = code_block(&stdout, "rust");
+    assert_eq!(
+        block,
+        "fn f() -> u32 {\n    let total = alpha + beta + gamma +\n    /* ↪ */ delta + epsilon;\n    total\n}\n"
+    );
+    assert!(block.lines().all(|line| line.chars().count() <= 40), "{}", block);
+}
+
+#[test]
+fn map_ext_processes_inc_files_as_php() {
+    let dir = project(&[("header.inc", "<?php\n// shared header\n$title = 'Home';\n"), ("notes.txt", "text\n")]);
+
+    let (stdout, _) = prompt(dir.path(), &[]);
+    assert!(!stdout.contains("header.inc"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["--map-ext", "inc=php", "--no-minify", "-r"]);
+    assert!(stdout.contains("## header.inc\n```php\n"), "{}", stdout);
+    assert!(stdout.contains("$title = 'Home';") && !stdout.contains("shared header"), "{}", stdout);
+    assert!(!stdout.contains("notes.txt"));
+
+    let output = run(dir.path(), &["--map-ext", "inc=klingon"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("unknown language `klingon`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn filter_cmd_replaces_each_file_with_the_command_output() {
+    let dir = project(&[("a.py", "x = 'hi'\n"), ("b.py", "y = 2\n")]);
+    let (plain, _) = prompt(dir.path(), &["-p"]);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--filter-cmd", "cat"]);
+    assert_eq!(stdout, plain);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--filter-cmd", "tr a-z A-Z"]);
+    assert!(stdout.contains("X='HI'") && stdout.contains("Y=2"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--filter-cmd", "echo \"# $CARGO_PROMPT_PATH\"; cat"]);
+    assert!(stdout.contains("# ./a.py\nx='hi'"), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn a_failing_filter_cmd_keeps_the_unfiltered_content() {
+    let dir = project(&[("a.py", "x = 1\n")]);
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--filter-cmd", "echo broken >&2; exit 3"]);
+    assert!(stdout.contains("```python\nx=1\n"), "{}", stdout);
+    assert!(stderr.contains("a.py") && stderr.contains("broken"), "{}", stderr);
+}
+
+#[test]
+fn multi_line_strings_survive_minifying() {
+    let block = processed("a.py", "HELP = \"\"\"usage:\n    tool [options]\n\"\"\"\n", "python", &["-p"]);
+    assert!(block.contains("\"\"\"usage:\n    tool [options]\n\"\"\""), "{}", block);
+    assert!(!block.contains("\\n"), "{}", block);
+}
+
+#[test]
+fn raw_strings_keep_comment_like_contents() {
+    let rust = "fn main() {\n    // gone\n    let s = r#\"// not a comment \"quoted\" /* nor this */\"#;\n}\n";
+    let block = processed("a.rs", rust, "rust", &["--no-minify", "-r"]);
+    assert_eq!(block, "fn main() {\n    let s = r#\"// not a comment \"quoted\" /* nor this */\"#;\n}\n");
+    let block = processed("a.rs", rust, "rust", &["-r"]);
+    assert!(block.contains("r#\"// not a comment \"quoted\" /* nor this */\"#"), "{}", block);
+
+    let csharp = "class A {\n    string p = @\"C:\\dir\\\"; // gone\n    string q = @\"say \"\"// hi\"\"\";\n}\n";
+    let block = processed("a.cs", csharp, "csharp", &["--csharp", "-r"]);
+    assert_eq!(block, "class A{string p=@\"C:\\dir\\\";string q=@\"say \"\"// hi\"\"\";}");
+}
+
+#[test]
+fn verbose_logs_skipped_files_to_stderr() {
+    let dir = project(&[("a.rs", "fn a() {}\n"), ("b.rs", "fn a() {}\n"), ("notes.txt", "text\n")]);
+
+    let (quiet, stderr) = prompt(dir.path(), &["--dedupe"]);
+    assert!(stderr.is_empty(), "{}", stderr);
+
+    let (stdout, stderr) = prompt(dir.path(), &["--dedupe", "--verbose"]);
+    assert_eq!(stdout, quiet, "logging stays out of the prompt");
+    assert!(stderr.contains("Processing ./a.rs as rust"), "{}", stderr);
+    assert!(stderr.contains("Skipping ./notes.txt: no enabled language matches it"), "{}", stderr);
+    assert!(stderr.contains("Found 3 file(s) in "), "{}", stderr);
+    assert!(!stderr.contains("has the same content as"), "duplicates are logged at the next level:\n{}", stderr);
+
+    let (_, stderr) = prompt(dir.path(), &["--dedupe", "--verbose", "--verbose"]);
+    assert!(stderr.contains("has the same content as"), "{}", stderr);
+}
+
+#[test]
+fn manifest_lists_included_files_with_their_hashes() {
+    use sha2::{Digest, Sha256};
+
+    let files = [("a.rs", "fn a() {}\n"), ("src/b.py", "x = 1\n"), ("notes.txt", "not included\n")];
+    let dir = project(&files);
+    let read = || {
+        prompt(dir.path(), &["-p", "--manifest", "manifest.json"]);
+        let manifest = fs::read_to_string(dir.path().join("manifest.json")).unwrap();
+        serde_json::from_str::<serde_json::Value>(&manifest).expect("the manifest is JSON")
+    };
+
+    let manifest = read();
+    assert_eq!(manifest["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest["arguments"], serde_json::json!(["-p", "--manifest", "manifest.json", "."]));
+    let mut listed: Vec<(String, String)> = manifest["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| (file["path"].as_str().unwrap().to_string(), file["sha256"].as_str().unwrap().to_string()))
+        .collect();
+    listed.sort();
+    let expected: Vec<(String, String)> = files[..2]
+        .iter()
+        .map(|(path, contents)| {
+            let hash = Sha256::digest(contents.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+            (path.to_string(), hash)
+        })
+        .collect();
+    assert_eq!(listed, expected);
+
+    assert_eq!(read(), manifest, "an unchanged tree gives the same manifest");
+}
+
+#[test]
+fn count_only_tabulates_lines_per_language() {
+    let dir = project(&[
+        ("a.py", "# header\nx = 1\n\ny = 2  # trailing\n"),
+        ("b.py", "z = 3\n"),
+        ("a.rs", "// c\nfn a() {}\n\n/* b\n   c */\n"),
+        ("notes.txt", "not counted\n"),
+    ]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--count-only"]);
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .filter(|line| !line.starts_with('-'))
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            vec!["Language", "files", "lines", "blank", "comment", "code"],
+            vec!["python", "2", "5", "1", "1", "3"],
+            vec!["rust", "1", "5", "1", "3", "1"],
+            vec!["Total", "3", "10", "2", "4", "4"],
+        ]
+    );
+}
+
+#[test]
+fn count_only_counts_files_read_with_another_encoding() {
+    let dir = project(&[("a.py", "x = 1\n")]);
+    fs::write(dir.path().join("cafe.py"), b"# caf\xe9\nname = 1\n").unwrap();
+
+    for encoding in ["lossy", "latin1"] {
+        let (stdout, _) = prompt(dir.path(), &["-p", "--count-only", "--encoding", encoding]);
+        let python: Vec<&str> = stdout.lines().find(|line| line.starts_with("python")).unwrap().split_whitespace().collect();
+        assert_eq!(python, ["python", "2", "3", "0", "1", "2"], "{}", encoding);
+    }
+}
+
+#[test]
+fn ini_comments_are_stripped() {
+    let source = "; top\n[server]\n# note\nport = 8080\nurl = \"a;b#c\"\n";
+    for file in ["app.ini", "setup.cfg"] {
+        let block = processed(file, source, "ini", &["--dotconfig", "-r"]);
+        assert_eq!(block, "\n[server]\n\nport = 8080\nurl = \"a;b#c\"\n");
+        assert_eq!(processed(file, source, "ini", &["--dotconfig"]), source, "kept verbatim without -r");
+    }
+}
+
+#[test]
+fn env_secrets_are_masked_without_redact() {
+    let dir = project(&[(".env", "API_KEY=sk-live-1234567890abcdef1234\nDEBUG=true\n"), ("config.env", "PASSWORD=hunter2hunter2\n")]);
+    let (stdout, stderr) = prompt(dir.path(), &["--dotconfig", "--hidden"]);
+    assert!(stdout.contains("## .env\n```dotenv\nAPI_KEY=***REDACTED***\nDEBUG=true\n"), "{}", stdout);
+    assert!(!stdout.contains("sk-live") && !stdout.contains("hunter2"), "{}", stdout);
+    assert!(stdout.contains("## config.env\n"), "{}", stdout);
+    assert!(stderr.contains("Redacted"), "{}", stderr);
+}
+
+#[test]
+fn make_recipes_keep_their_tabs() {
+    let makefile = "# build everything\nall: build\n\nbuild:\n\tcargo build   --release\n\t@echo done\n";
+    for file in ["Makefile", "rules.mk"] {
+        let block = processed(file, makefile, "makefile", &["--make", "-r"]);
+        assert_eq!(block, "all: build\nbuild:\n\tcargo build --release\n\t@echo done");
+    }
+
+    let cmake = "# project\ncmake_minimum_required(VERSION 3.20)\n\n\tproject(demo   C)\n";
+    for file in ["CMakeLists.txt", "tools/find.cmake"] {
+        let block = processed(file, cmake, "cmake", &["--make", "-r"]);
+        assert_eq!(block, "cmake_minimum_required(VERSION 3.20)\nproject(demo C)");
+    }
+}
+
+#[test]
+fn modified_within_keeps_only_recent_files() {
+    let dir = project(&[("old.py", "old = 1\n"), ("new.py", "new = 1\n")]);
+    let two_days_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 24 * 60 * 60);
+    fs::File::options().write(true).open(dir.path().join("old.py")).unwrap().set_modified(two_days_ago).unwrap();
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--modified-within", "24h"]);
+    assert!(stdout.contains("## new.py") && !stdout.contains("old.py"), "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-p", "--modified-within", "2d 1h"]);
+    assert!(stdout.contains("## new.py") && stdout.contains("## old.py"), "{}", stdout);
+
+    let output = run(dir.path(), &["--modified-within", "yesterday"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn direct_and_cargo_invocations_give_the_same_prompt() {
+    let dir = project(&[("crates/core/src/lib.rs", "pub fn core() {}\n"), ("crates/core/a.py", "a = 1\n"), ("top.rs", "fn top() {}\n")]);
+    let direct = |args: &[&str]| {
+        let output = cargo_prompt()
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .expect("couldn't run cargo-prompt");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    for args in [&["crates/core"][..], &["-p", "crates/core"], &["crates/core", "-p", "--no-minify"], &[]] {
+        let (via_cargo, _) = prompt_on(dir.path(), args);
+        assert_eq!(direct(args), via_cargo, "{:?}", args);
+    }
+    assert!(direct(&["crates/core"]).contains("pub fn core"));
+    assert!(!direct(&["crates/core"]).contains("fn top"));
+}
+
+#[test]
+fn compact_headers_are_smaller_and_still_delimit_files() {
+    let dir = project(&[
+        ("a.py", "DOC = \"\"\"\n```rust\nx\n```\n\"\"\"\n"),
+        ("b.py", "b = 1\n"),
+        ("src/c.py", "c = 1\n"),
+    ]);
+    let (full, _) = prompt(dir.path(), &["-p", "--no-minify"]);
+    let (compact, _) = prompt(dir.path(), &["-p", "--no-minify", "--compact"]);
+    assert!(compact.len() < full.len(), "{} >= {} bytes", compact.len(), full.len());
+    assert!(!compact.contains("## a.py"), "{}", compact);
+
+    // Each opening fence names its file, and a longer fence wraps content with fences of its own
+    assert!(compact.contains("````python a.py\nDOC = \"\"\"\n```rust\nx\n```\n\"\"\"\n\n````\n"), "{}", compact);
+    assert!(compact.contains("```python b.py\nb = 1\n\n```\n"), "{}", compact);
+    assert!(compact.contains("```python src/c.py\nc = 1\n\n```\n"), "{}", compact);
+}
+
+#[test]
+fn yaml_documents_and_indentation_survive() {
+    let yaml = "# first\nname: app\nservices:\n  web:\n    image: nginx  # pinned later\n\n\n    ports:\n      - \"80:80\"\n---\nkind: Job\nspec:\n  script: |\n    # runs first\n    make test\n...\n";
+    let block = processed("deploy.yml", yaml, "yaml", &["--yaml", "-r"]);
+    assert_eq!(
+        block,
+        "name: app\nservices:\n  web:\n    image: nginx\n\n    ports:\n      - \"80:80\"\n---\nkind: Job\nspec:\n  script: |\n    # runs first\n    make test\n..."
+    );
+    assert_eq!(processed("deploy.yaml", yaml, "yaml", &["--yaml", "--no-minify"]), yaml);
+}
+
+#[test]
+fn statements_split_across_lines_keep_a_separating_space() {
+    let source = "int\nmain(void)\n{\n    unsigned\n    long total = 0;\n    return\n        total;\n}\n";
+    assert_eq!(processed("a.c", source, "cpp", &["-c"]), "int main(void){unsigned long total=0;return total;}");
+}
+
+#[test]
+fn template_substitutes_project_files_and_totals() {
+    let template = "# {project} ({total_files} files, ~{total_tokens} tokens)\n{{ for file in files }}<file path=\"{file.path}\" language=\"{file.language}\" tokens=\"{file.tokens}\" lines=\"{file.lines}\">\n{file.content}\n</file>\n{{ endfor }}";
+    let dir = project(&[("prompt.tpl", template), ("a.py", "x = 1 < 2\n"), ("notes.txt", "text\n")]);
+    let (stdout, _) = prompt(dir.path(), &["-p", "--no-minify", "--title", "Demo", "--template", "prompt.tpl"]);
+
+    let content = "x = 1 < 2\n";
+    let expected = format!(
+        "<file path=\"a.py\" language=\"python\" tokens=\"{}\" lines=\"1\">\n{}\n</file>\n",
+        tokens(content),
+        content
+    );
+    assert!(stdout.starts_with("# Demo (1 files, ~"), "{}", stdout);
+    assert!(stdout.ends_with(&expected), "values are inserted unescaped:\n{}", stdout);
+
+    let output = run(dir.path(), &["--template", "missing.tpl"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn no_tests_and_only_tests_split_by_convention() {
+    let dir = project(&[
+        ("server.go", "package main\n"),
+        ("server_test.go", "package main // test\n"),
+        ("app.py", "app = 1\n"),
+        ("test_app.py", "test = 1\n"),
+        ("button.ts", "export const button = 1;\n"),
+        ("button.spec.ts", "export const spec = 1;\n"),
+    ]);
+    let headers = |stdout: &str| {
+        let mut headers: Vec<String> = stdout.lines().filter_map(|line| line.strip_prefix("## ")).map(String::from).collect();
+        headers.retain(|header| header != "Stats");
+        headers.sort();
+        headers
+    };
+
+    let (stdout, _) = prompt(dir.path(), &["-g", "-p", "--typescript", "--no-tests"]);
+    assert_eq!(headers(&stdout), ["app.py", "button.ts", "server.go"]);
+    let (stdout, _) = prompt(dir.path(), &["-g", "-p", "--typescript", "--only-tests"]);
+    assert_eq!(headers(&stdout), ["button.spec.ts", "server_test.go", "test_app.py"]);
+}
+
+#[test]
+fn model_sets_the_budget_and_warns_when_over_it() {
+    let big = "x = 1\n".repeat(6_000);
+    let dir = project(&[("a.py", "a = 1\n"), ("big.py", &big)]);
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--no-minify", "--model", "gpt-4"]);
+    assert!(tokens(&stdout) <= 8_192, "~{} tokens", tokens(&stdout));
+    assert!(stdout.contains("a = 1") && stdout.contains("within the 8192-token budget:\n- big.py\n"), "{}", stdout);
+    assert!(stderr.contains("exceeds gpt-4's context window of 8192 tokens; 1 file(s) were left out"), "{}", stderr);
+
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--no-minify", "--model", "gpt-4o"]);
+    assert!(stdout.contains("## big.py") && !stderr.contains("context window"), "{}", stderr);
+
+    // An explicit --max-tokens takes precedence over the model's window
+    let (stdout, stderr) = prompt(dir.path(), &["-p", "--no-minify", "--model", "gpt-4", "--max-tokens", "100000"]);
+    assert!(stdout.contains("## big.py") && !stderr.contains("context window"), "{}", stderr);
+
+    let output = run(dir.path(), &["--model", "gpt-5000"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("known models are gpt-4o"));
+}
+
+#[test]
+fn objc_files_get_their_own_fence() {
+    let dir = project(&[
+        ("View.mm", "#import <UIKit/UIKit.h>\n// view\n@implementation View\n@end\n"),
+        ("Model.m", "@implementation Model\n@end\n"),
+        ("View.h", "@interface View\n@end\n"),
+        ("analysis.m", "x = 1;\n"),
+    ]);
+    let fence_of = |stdout: &str, file: &str| {
+        let heading = format!("## {}\n```", file);
+        let rest = &stdout[stdout.find(&heading)? + heading.len()..];
+        Some(rest[..rest.find('\n').unwrap()].to_string())
+    };
+
+    let (stdout, _) = prompt(dir.path(), &["--objc", "-r"]);
+    assert!(stdout.contains("## View.mm\n```objectivec\n#import <UIKit/UIKit.h>\n@implementation View\n@end\n```"), "{}", stdout);
+    for file in ["Model.m", "View.h", "analysis.m"] {
+        assert_eq!(fence_of(&stdout, file).as_deref(), Some("objectivec"), "{}", file);
+    }
+
+    // `.m` is MATLAB with -m alone, and `.h` is C once C is collected
+    let (stdout, _) = prompt(dir.path(), &["-m", "-c"]);
+    assert_eq!(fence_of(&stdout, "analysis.m").as_deref(), Some("matlab"));
+    assert_eq!(fence_of(&stdout, "View.h").as_deref(), Some("cpp"));
+    assert_eq!(fence_of(&stdout, "View.mm"), None, "{}", stdout);
+
+    let (stdout, _) = prompt(dir.path(), &["-a"]);
+    assert_eq!(fence_of(&stdout, "View.mm").as_deref(), Some("objectivec"));
+    assert_eq!(fence_of(&stdout, "Model.m").as_deref(), Some("objectivec"));
+}
+
+#[test]
+fn the_cache_lives_in_cargo_prompt_cache_dir() {
+    let dir = project(&[("a.rs", "fn a() {}\n"), ("b.rs", "fn b() {}\n")]);
+    let cache = tempfile::tempdir().unwrap();
+    let run = |args: &[&str]| {
+        let output = cargo_prompt()
+            .env("CARGO_PROMPT_CACHE_DIR", cache.path())
+            .args(["prompt", "--verbose", "--verbose"])
+            .args(args)
+            .arg(".")
+            .current_dir(dir.path())
+            .output()
+            .expect("couldn't run cargo-prompt");
+        assert!(output.status.success());
+        (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+    let entries = || fs::read_dir(cache.path()).unwrap().filter(|entry| entry.as_ref().unwrap().path().is_dir()).count();
+
+    let (uncached, _) = run(&["--no-cache"]);
+    assert_eq!(entries(), 0);
+    let (first, stderr) = run(&[]);
+    assert_eq!(entries(), 2);
+    assert!(!stderr.contains("cached result"), "{}", stderr);
+    let (second, stderr) = run(&[]);
+    assert!(stderr.contains("Using the cached result for ./a.rs"), "{}", stderr);
+    assert_eq!(first, uncached);
+    assert_eq!(second, uncached);
+}